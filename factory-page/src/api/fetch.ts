@@ -76,6 +76,19 @@ export class FetchApiService implements ApiService {
     return this.fetchPostJson("/light/test", request);
   }
 
+  async identify(): Promise<void> {
+    return this.withLock(async () => {
+      const response = await fetch(`${this.baseUrl}/light/identify`, {
+        method: "POST",
+      });
+      if (!response.ok) {
+        throw new Error(`Failed to identify: ${response.statusText}`, {
+          cause: response.statusText,
+        });
+      }
+    });
+  }
+
   async getSystemInformation(): Promise<SystemInformation> {
     return this.fetchGetJson<SystemInformation>("/system");
   }
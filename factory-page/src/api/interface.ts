@@ -12,6 +12,7 @@ export interface ApiService {
   saveConfiguration(configuration: Configuration): Promise<void>;
   setLightConfiguration(light: LightConfiguration): Promise<void>;
   testColor(request: LightTestRequest): Promise<void>;
+  identify(): Promise<void>;
   getSystemInformation(): Promise<SystemInformation>;
   updateFirmware(file: File, onProgress: ProgressCallback): Promise<void>;
   bootSystem(): Promise<void>;
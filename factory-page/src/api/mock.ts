@@ -52,12 +52,21 @@ export class MockApiService implements ApiService {
     return;
   }
 
+  async identify(): Promise<void> {
+    console.log(`[mock] identifying device`);
+    await simulateNetworkDelay();
+    return;
+  }
+
   async getSystemInformation(): Promise<SystemInformation> {
     console.log(`[mock] getting system information`);
     await simulateNetworkDelay();
     return {
       build_version: "0d35914-2025-12-23T11:16:09+0000",
       mac_address: [160, 183, 101, 22, 48, 84],
+      running_partition: "factory",
+      pending_light_intents: 0,
+      dropped_light_intents: 0,
     };
   }
 
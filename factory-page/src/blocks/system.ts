@@ -3,12 +3,17 @@ type MacAddress = [number, number, number, number, number, number];
 export interface SystemInformation {
   build_version: string;
   mac_address: MacAddress;
+  running_partition: "factory" | "ota_0" | "ota_1" | null;
+  pending_light_intents: number;
+  dropped_light_intents: number;
 }
 
 export class SystemBlock {
   private buildVersion: HTMLDivElement;
   private buildDate: HTMLDivElement;
   private macAddress: HTMLDivElement;
+  private runningPartition: HTMLDivElement;
+  private lightQueue: HTMLDivElement;
   private block: HTMLElement;
   private otaButton: HTMLButtonElement;
   private otaFile: HTMLInputElement;
@@ -27,6 +32,8 @@ export class SystemBlock {
     this.buildVersion = $("#build-version");
     this.buildDate = $("#build-date");
     this.macAddress = $("#mac-address");
+    this.runningPartition = $("#running-partition");
+    this.lightQueue = $("#light-queue");
     this.otaButton = $("#button-ota");
     this.otaFile = $("#ota-file");
     this.bootButton = $("#button-boot");
@@ -55,6 +62,8 @@ export class SystemBlock {
     this.buildVersion.textContent = commitHash;
     this.buildDate.textContent = buildDate.toLocaleString();
     this.macAddress.textContent = formatMacAddress(system.mac_address);
+    this.runningPartition.textContent = formatRunningPartition(system.running_partition);
+    this.lightQueue.textContent = `${system.pending_light_intents} в очереди, ${system.dropped_light_intents} пропущено`;
   }
 
   public unlock() {
@@ -76,6 +85,19 @@ function parseBuildVersion(version: string): [string, Date] {
   return [hash, new Date(date)];
 }
 
+function formatRunningPartition(partition: SystemInformation["running_partition"]): string {
+  switch (partition) {
+    case "factory":
+      return "Заводской";
+    case "ota_0":
+      return "Система (ota_0)";
+    case "ota_1":
+      return "Система (ota_1)";
+    default:
+      return "Неизвестно";
+  }
+}
+
 function formatMacAddress(macAddress: MacAddress): string {
   return macAddress.map((byte) => byte.toString(16).padStart(2, "0")).join(":");
 }
@@ -39,6 +39,7 @@ async function main() {
   const testGreen = document.getElementById("test-green");
   const testBlue = document.getElementById("test-blue");
   const testWhite = document.getElementById("test-white");
+  const testIdentify = document.getElementById("test-identify");
 
   const testColor = (r: number, g: number, b: number, brightness: number) => {
     api.testColor({ r, g, b, brightness }).catch(console.error);
@@ -58,6 +59,9 @@ async function main() {
   testWhite?.addEventListener("click", () =>
     testColor(255, 255, 255, testBrightness)
   );
+  testIdentify?.addEventListener("click", () => {
+    api.identify().catch(console.error);
+  });
 
   async function onBoot() {
     await api.bootSystem();
@@ -3,6 +3,9 @@ export type MacAddress = [number, number, number, number, number, number];
 export interface SystemInformation {
   build_version: string;
   mac_address: MacAddress;
+  running_partition: "factory" | "ota_0" | "ota_1" | null;
+  pending_light_intents: number;
+  dropped_light_intents: number;
 }
 
 export interface WifiConfiguration {
@@ -11,6 +11,7 @@ use crate::{
             ConfigurationServicePort,
             ConfigurationUsecasesPort,
             ConfigurationWriter,
+            FactoryResetter,
             LightConfigurationSetter,
             LightError,
             LightStateHandler,
@@ -91,6 +92,19 @@ impl<P: PersistentDataHandler, S: LightStateHandler> LightConfigurationSetter
     }
 }
 
+impl<P: PersistentDataHandler, S: LightStateHandler> FactoryResetter
+    for ConfigurationUsecases<P, S>
+{
+    fn factory_reset(&mut self) -> Result<(), ConfigurationError> {
+        self.configuration
+            .erase_persistent_data()
+            .map_err(|_| ConfigurationError::StorageBusy)?;
+        #[cfg(feature = "log")]
+        println!("configuration: factory reset requested");
+        Ok(())
+    }
+}
+
 impl<P: PersistentDataHandler, S: LightStateHandler> ConfigurationUsecasesPort
     for ConfigurationUsecases<P, S>
 {
@@ -4,13 +4,18 @@ use alloc::boxed::Box;
 use core::{future::Future, pin::Pin};
 
 use crate::{
-    core::net::http::HttpConnection,
-    domain::ports::{
-        BootSectorSelector,
-        FirmwareError,
-        FirmwareHandler,
-        FirmwareUsecasesPort,
-        HttpFirmwareUpdater,
+    core::{md5::MD5_LEN, net::http::HttpConnection},
+    domain::{
+        dto::OtaImageInfo,
+        entity::AppPartition,
+        ports::{
+            BootSectorSelector,
+            FirmwareError,
+            FirmwareHandler,
+            FirmwareUsecasesPort,
+            HttpFirmwareUpdater,
+            RunningPartitionReader,
+        },
     },
 };
 
@@ -34,6 +39,12 @@ impl<P: FirmwareHandler> BootSectorSelector for FirmwareUsecases<P> {
     }
 }
 
+impl<P: FirmwareHandler> RunningPartitionReader for FirmwareUsecases<P> {
+    fn running_partition(&self) -> Option<AppPartition> {
+        self.firmware.running_partition()
+    }
+}
+
 impl<P: FirmwareHandler> FirmwareHandler for FirmwareUsecases<P> {}
 
 impl<P: FirmwareHandler> HttpFirmwareUpdater for FirmwareUsecases<P> {
@@ -46,6 +57,18 @@ impl<P: FirmwareHandler> HttpFirmwareUpdater for FirmwareUsecases<P> {
     {
         self.firmware.update_firmware_from_http(conn)
     }
+
+    fn validate_firmware_from_http<'s, 'c>(
+        &'s self,
+        conn: &'c mut HttpConnection<'_>,
+        expected_md5: Option<[u8; MD5_LEN]>,
+    ) -> Pin<Box<dyn Future<Output = Result<OtaImageInfo, FirmwareError>> + 's>>
+    where
+        'c: 's,
+    {
+        self.firmware
+            .validate_firmware_from_http(conn, expected_md5)
+    }
 }
 
 impl<P: FirmwareHandler> FirmwareUsecasesPort for FirmwareUsecases<P> {}
@@ -7,6 +7,7 @@ use crate::{
             LightConfigChanger,
             light::{
                 LightError,
+                LightIdentifier,
                 LightStateChanger,
                 LightStateHandler,
                 LightStateReader,
@@ -48,6 +49,14 @@ impl<S: LightStateHandler, P: PersistentDataHandler> LightStateReader
     }
 }
 
+impl<S: LightStateHandler, P: PersistentDataHandler> LightIdentifier
+    for LightUsecases<S, P>
+{
+    fn identify(&self, blinks: u8) -> Result<(), LightError> {
+        self.state.identify(blinks)
+    }
+}
+
 impl<S: LightStateHandler, P: PersistentDataHandler> LightUsecasesPort
     for LightUsecases<S, P>
 {
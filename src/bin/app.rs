@@ -12,11 +12,14 @@ use myrtio_esp_light::{
     app::{FirmwareUsecases, LightUsecases},
     config::hostname,
     controllers::app::{self, init_app_controllers},
-    domain::ports::{
-        BootSectorSelector,
-        LightConfigChanger,
-        LightStateChanger,
-        PersistentDataReader,
+    domain::{
+        entity::LightState,
+        ports::{
+            BootSectorSelector,
+            LightConfigChanger,
+            LightStateChanger,
+            PersistentDataReader,
+        },
     },
     infrastructure::{
         adapters::{self},
@@ -59,10 +62,18 @@ async fn main(spawner: Spawner) -> ! {
         myrtio_esp_light::led_gpio!(peripherals),
     );
 
+    // Register the boot before applying persisted state, so a state that
+    // crashes the firmware is skipped after a few attempts
+    let safe_mode = services::init_boot_guard(spawner);
+
     // Configure light
-    let (light_state, config) = persistence_service
+    let (mut light_state, config) = persistence_service
         .read_persistent_data()
         .unwrap_or_default();
+    if safe_mode {
+        println!("app: repeated boot failures; starting in safe mode");
+        light_state = LightState::safe_mode();
+    }
     light_service
         .apply_light_intent(light_state.into())
         .unwrap();
@@ -99,11 +110,13 @@ async fn main(spawner: Spawner) -> ! {
     .await;
 
     // Initialize adapters
-    let mqtt_module = init_app_controllers(light_usecases, firmware_usecases);
+    let mqtt_module =
+        init_app_controllers(spawner, light_usecases, firmware_usecases);
     adapters::bind_boot_button(
         peripherals.IO_MUX,
         peripherals.GPIO0,
-        app::handle_boot_button_click,
+        app::handle_boot_button_short_press,
+        app::handle_boot_button_long_press,
     );
     adapters::start_mqtt_client(spawner, stack, mqtt_module, config.mqtt);
 
@@ -99,7 +99,8 @@ async fn main(spawner: Spawner) -> ! {
     adapters::bind_boot_button(
         peripherals.IO_MUX,
         peripherals.GPIO0,
-        factory::handle_boot_button_click,
+        factory::handle_boot_button_short_press,
+        factory::handle_boot_button_long_press,
     );
 
     let stack = drivers::start_wifi_ap(
@@ -26,6 +26,33 @@ pub const TEMPERATURE_MIN_KELVIN: u16 = 1500;
 /// Debounce time for writing light state to the storage
 pub const LIGHT_STATE_WRITE_DEBOUNCE: Duration = Duration::from_millis(5000);
 
+/// Number of blinks shown when identify is requested from the factory page
+pub const IDENTIFY_BLINKS: u8 = 3;
+
+/// Duration of each on and off phase of an identify blink
+pub const IDENTIFY_BLINK_PHASE: Duration = Duration::from_millis(1000);
+
+/// Skip sending frames identical to the last one (e.g. while the strip is off)
+pub const LED_SKIP_UNCHANGED_FRAMES: bool = true;
+
+/// Minimum boot button hold time, shorter presses are contact bounces
+pub const BUTTON_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Boot button hold time that counts as a long press
+pub const BUTTON_LONG_PRESS: Duration = Duration::from_millis(2000);
+
+/// Consecutive unstable boots after which the app starts in safe mode
+pub const BOOT_SAFE_MODE_THRESHOLD: u32 = 3;
+
+/// Uptime after which a boot is considered stable
+pub const BOOT_STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// Interval of the periodic Home Assistant state publish
+pub const STATE_PUBLISH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upper bound of the per-device phase offset of the state publish
+pub const STATE_PUBLISH_JITTER: Duration = Duration::from_secs(5);
+
 /// Maximum supported LED count
 pub const LED_COUNT_MAX: usize = 128;
 
@@ -182,6 +209,21 @@ pub fn hardware_id() -> u32 {
     u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]])
 }
 
+/// Get the per-device phase offset of the periodic state publish
+///
+/// Every MQTT session starts this much later, which shifts the publish
+/// ticker of the session. Devices booted together keep the nominal period
+/// but publish at different points of it.
+pub fn state_publish_phase() -> Duration {
+    state_publish_phase_for(hardware_id())
+}
+
+fn state_publish_phase_for(hardware_id: u32) -> Duration {
+    // Fibonacci hashing spreads sequential ids from one batch over the band
+    let hash = u64::from(hardware_id.wrapping_mul(0x9E37_79B9));
+    Duration::from_millis((hash * STATE_PUBLISH_JITTER.as_millis()) >> 32)
+}
+
 /// Get the MAC address
 pub fn mac_address() -> [u8; 6] {
     esp_hal::efuse::Efuse::mac_address()
@@ -218,6 +260,27 @@ pub fn device_id() -> String<32> {
     device_id
 }
 
+const MQTT_CLIENT_ID_PREFIX: &str = "myrtio-light";
+
+/// Get the MQTT client id
+///
+/// Uses the full factory MAC, so ids are unique per device and stay the
+/// same across reflashes and factory resets.
+pub fn mqtt_client_id() -> String<32> {
+    mqtt_client_id_from_mac(mac_address())
+}
+
+fn mqtt_client_id_from_mac(mac: [u8; 6]) -> String<32> {
+    use core::fmt::Write;
+    let mut client_id = String::<32>::new();
+    let _ = write!(
+        client_id,
+        "{MQTT_CLIENT_ID_PREFIX}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    );
+    client_id
+}
+
 /// Get the LED GPIO pin from the peripherals
 #[macro_export]
 macro_rules! led_gpio {
@@ -225,3 +288,45 @@ macro_rules! led_gpio {
         $p.GPIO25
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mqtt_client_id_is_unique_per_mac() {
+        let mac = [0x24, 0x0A, 0xC4, 0x12, 0x34, 0x56];
+        let id = mqtt_client_id_from_mac(mac);
+        assert_eq!(id.as_str(), "myrtio-light-240ac4123456");
+        assert_eq!(mqtt_client_id_from_mac(mac), id);
+
+        // Devices from the same batch differ only in the last bytes
+        let neighbour = [0x24, 0x0A, 0xC4, 0x12, 0x34, 0x57];
+        assert_ne!(mqtt_client_id_from_mac(neighbour), id);
+        // The hardware id ignores the first two bytes, the client id must not
+        let other_vendor = [0x30, 0xAE, 0xC4, 0x12, 0x34, 0x56];
+        assert_ne!(mqtt_client_id_from_mac(other_vendor), id);
+    }
+
+    #[test]
+    fn state_publish_phase_stays_within_the_band() {
+        let ids = [0, 1, 0xFFFF, 0x1234_5678, u32::MAX];
+        for id in ids {
+            assert!(state_publish_phase_for(id) < STATE_PUBLISH_JITTER);
+            assert_eq!(state_publish_phase_for(id), state_publish_phase_for(id));
+        }
+    }
+
+    #[test]
+    fn sequential_devices_get_spread_phases() {
+        let mut phases: [u64; 8] = core::array::from_fn(|i| {
+            let id = 0x00C4_1200 + u32::try_from(i).unwrap();
+            state_publish_phase_for(id).as_millis()
+        });
+        phases.sort_unstable();
+        let min_gap = STATE_PUBLISH_JITTER.as_millis() / 20;
+        for pair in phases.windows(2) {
+            assert!(pair[1] - pair[0] >= min_gap, "{phases:?}");
+        }
+    }
+}
@@ -1,8 +1,21 @@
-use super::FIRMWARE_USECASES;
-use crate::domain::ports::BootSectorSelector as _;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
 
-/// Handler for boot button click event
-pub fn handle_boot_button_click() {
+use super::{FIRMWARE_USECASES, LIGHT_USECASES};
+use crate::domain::{dto::LightChangeIntent, ports::BootSectorSelector as _};
+
+/// Pending light toggle request from the boot button
+static TOGGLE_LIGHT_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Handler for boot button short press: toggle the light
+///
+/// Runs in the GPIO interrupt, so the toggle itself is deferred to
+/// [`light_toggle_task`].
+pub fn handle_boot_button_short_press() {
+    TOGGLE_LIGHT_SIGNAL.signal(());
+}
+
+/// Handler for boot button long press: reboot into the factory firmware
+pub fn handle_boot_button_long_press() {
     FIRMWARE_USECASES.lock(|cell| {
         let mut cell = cell.borrow_mut();
         let firmware = cell.as_mut().unwrap();
@@ -10,3 +23,25 @@ pub fn handle_boot_button_click() {
         firmware.boot_factory().unwrap();
     });
 }
+
+/// Task toggling the light on boot button short presses
+#[embassy_executor::task]
+pub(super) async fn light_toggle_task() {
+    loop {
+        TOGGLE_LIGHT_SIGNAL.wait().await;
+
+        LIGHT_USECASES.lock(|cell| {
+            let mut cell = cell.borrow_mut();
+            let Some(usecases) = cell.as_mut() else {
+                return;
+            };
+
+            let power = usecases.get_light_state().power;
+            let intent = LightChangeIntent::new().with_power(!power);
+            if let Err(_e) = usecases.apply_intent_and_persist(intent) {
+                #[cfg(feature = "log")]
+                esp_println::println!("button: failed to toggle light: {:?}", _e);
+            }
+        });
+    }
+}
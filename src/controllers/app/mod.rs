@@ -3,7 +3,8 @@ mod mqtt_homeassistant;
 
 use core::cell::RefCell;
 
-pub use button::handle_boot_button_click;
+pub use button::{handle_boot_button_long_press, handle_boot_button_short_press};
+use embassy_executor::Spawner;
 use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
 use mqtt_homeassistant::init_mqtt_homeassistant_module;
 pub use myrtio_mqtt::runtime::MqttModule;
@@ -26,6 +27,7 @@ pub(super) static FIRMWARE_USECASES: Mutex<
 
 /// Initialize the app controllers with it's dependencies
 pub fn init_app_controllers(
+    spawner: Spawner,
     light: LightUsecasesPortRef,
     firmware: FirmwareUsecases<FirmwareService>,
 ) -> &'static mut dyn MqttModule {
@@ -37,5 +39,9 @@ pub fn init_app_controllers(
         cell.borrow_mut().replace(firmware);
     });
 
+    spawner
+        .spawn(button::light_toggle_task())
+        .expect("Failed to spawn light toggle task");
+
     init_mqtt_homeassistant_module()
 }
@@ -4,7 +4,8 @@
 //! It creates and configures the `HaModule` with the appropriate entities and
 //! callbacks.
 
-use embassy_time::Duration;
+use core::fmt::Write as _;
+
 use heapless::String;
 use myrtio_light_composer::EffectId;
 use myrtio_mqtt::runtime::MqttModule;
@@ -19,7 +20,7 @@ use myrtio_mqtt_homeassistant::{
 };
 use static_cell::StaticCell;
 
-use super::LIGHT_USECASES;
+use super::{FIRMWARE_USECASES, LIGHT_USECASES};
 use crate::{
     config::{
         self,
@@ -29,7 +30,15 @@ use crate::{
         TEMPERATURE_MAX_KELVIN,
         TEMPERATURE_MIN_KELVIN,
     },
-    domain::dto::LightChangeIntent,
+    domain::{
+        dto::LightChangeIntent,
+        entity::{
+            AppPartition,
+            ColorMode as StateColorMode,
+            LightState as DomainLightState,
+        },
+        ports::RunningPartitionReader,
+    },
     mk_static,
 };
 
@@ -55,15 +64,45 @@ fn get_light_state() -> LightState {
     if state.power {
         let effect_id =
             EffectId::from_raw(state.mode_id).unwrap_or(EffectId::Static);
-        return LightState::on()
+        let light_state = LightState::on()
             .with_brightness(state.brightness)
-            .with_rgb(state.color.0, state.color.1, state.color.2)
             .with_effect(effect_id.as_str());
+        return match reported_color(&state) {
+            ReportedColor::Rgb(r, g, b) => light_state.with_rgb(r, g, b),
+            ReportedColor::ColorTemp(kelvin) => light_state.with_color_temp(kelvin),
+        };
     }
 
     LightState::off()
 }
 
+/// Color reported to Home Assistant, which also sets the HA `color_mode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportedColor {
+    Rgb(u8, u8, u8),
+    ColorTemp(u16),
+}
+
+/// Report the color of the active color mode only
+fn reported_color(state: &DomainLightState) -> ReportedColor {
+    match state.color_mode {
+        StateColorMode::Rgb => {
+            ReportedColor::Rgb(state.color.0, state.color.1, state.color.2)
+        }
+        StateColorMode::Temperature => ReportedColor::ColorTemp(state.color_temp),
+    }
+}
+
+/// Firmware version shown in Home Assistant, with the partition it runs from
+fn firmware_version(partition: Option<AppPartition>) -> String<48> {
+    let mut version = String::new();
+    let _ = version.push_str(BUILD_VERSION);
+    if let Some(partition) = partition {
+        let _ = write!(version, " ({})", partition.as_str());
+    }
+    version
+}
+
 /// Handle light commands from Home Assistant
 fn handle_light_command(cmd: &LightCommand) {
     let mut intent = LightChangeIntent::new();
@@ -93,9 +132,12 @@ fn handle_light_command(cmd: &LightCommand) {
     LIGHT_USECASES.lock(|cell| {
         let mut cell_ref = cell.borrow_mut();
         let usecases = cell_ref.as_mut().unwrap();
-        usecases
-            .apply_intent_and_persist(intent)
-            .expect("Failed to apply intent");
+        // A full engine queue drops the command rather than the connection;
+        // the next state publish reports what is actually shown.
+        if let Err(_e) = usecases.apply_intent_and_persist(intent) {
+            #[cfg(feature = "log")]
+            esp_println::println!("ha: light command dropped: {:?}", _e);
+        }
     });
 }
 
@@ -117,6 +159,13 @@ pub(super) fn init_mqtt_homeassistant_module() -> &'static mut dyn MqttModule {
         BUILD_VERSION.len()
     );
 
+    let running_partition = FIRMWARE_USECASES.lock(|cell| {
+        cell.borrow()
+            .as_ref()
+            .and_then(RunningPartitionReader::running_partition)
+    });
+    let sw_version = mk_static!(String<48>, firmware_version(running_partition));
+
     let device = mk_static!(
         Device<'static>,
         Device::builder()
@@ -124,7 +173,7 @@ pub(super) fn init_mqtt_homeassistant_module() -> &'static mut dyn MqttModule {
             .name(device_name.as_str())
             .manufacturer(Some(DEVICE_MANUFACTURER))
             .model(Some(DEVICE_MODEL))
-            .sw_version(Some(BUILD_VERSION))
+            .sw_version(Some(sw_version.as_str()))
             .build()
     );
     let supported_effects = mk_static!(
@@ -155,7 +204,7 @@ pub(super) fn init_mqtt_homeassistant_module() -> &'static mut dyn MqttModule {
         .optimistic(false)
         .build();
 
-    let mut module = HomeAssistantModule::new(Duration::from_secs(30));
+    let mut module = HomeAssistantModule::new(config::STATE_PUBLISH_INTERVAL);
 
     let light_registration = LightRegistration {
         entity: light_entity,
@@ -171,3 +220,30 @@ pub(super) fn init_mqtt_homeassistant_module() -> &'static mut dyn MqttModule {
         HOME_ASSISTANT_MODULE.uninit().write(module);
     module as &'static mut dyn MqttModule
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb_state_reports_rgb() {
+        let state = DomainLightState {
+            color: (255, 128, 0),
+            color_temp: 2700,
+            color_mode: StateColorMode::Rgb,
+            ..DomainLightState::new()
+        };
+        assert_eq!(reported_color(&state), ReportedColor::Rgb(255, 128, 0));
+    }
+
+    #[test]
+    fn temperature_state_reports_color_temp() {
+        let state = DomainLightState {
+            color: (255, 128, 0),
+            color_temp: 2700,
+            color_mode: StateColorMode::Temperature,
+            ..DomainLightState::new()
+        };
+        assert_eq!(reported_color(&state), ReportedColor::ColorTemp(2700));
+    }
+}
@@ -1,7 +1,7 @@
-use crate::controllers::factory::FIRMWARE_USECASES;
+use crate::controllers::factory::{CONFIGURATION_USECASES, FIRMWARE_USECASES};
 
-/// Handler for boot button click event
-pub fn handle_boot_button_click() {
+/// Handler for boot button short press: boot the system firmware
+pub fn handle_boot_button_short_press() {
     let Ok(guard) = FIRMWARE_USECASES.try_lock() else {
         return;
     };
@@ -9,3 +9,23 @@ pub fn handle_boot_button_click() {
     let firmware = firmware_ref.as_mut().unwrap();
     firmware.boot_system().unwrap();
 }
+
+/// Handler for boot button long press: factory reset
+///
+/// Only requests the reset; the persistence task erases the stored
+/// configuration and light state, then reboots into the factory firmware.
+pub fn handle_boot_button_long_press() {
+    let Ok(guard) = CONFIGURATION_USECASES.try_lock() else {
+        #[cfg(feature = "log")]
+        esp_println::println!("button: factory reset ignored, configuration busy");
+        return;
+    };
+    let mut usecases_ref = guard.borrow_mut();
+    let Some(usecases) = usecases_ref.as_mut() else {
+        return;
+    };
+    if let Err(_e) = usecases.factory_reset() {
+        #[cfg(feature = "log")]
+        esp_println::println!("button: factory reset failed: {:?}", _e);
+    }
+}
@@ -18,21 +18,35 @@ use crate::{
         unpack_color_correction_rgb24,
         unpack_color_order,
     },
-    core::net::http::{
-        ContentEncoding,
-        ContentHeaders,
-        ContentType,
-        Error as HttpError,
-        HttpConnection,
-        HttpHandler,
-        HttpMethod,
-        HttpResult,
-        ResponseHeaders,
-        TextEncoding,
+    core::{
+        md5,
+        net::http::{
+            ContentEncoding,
+            ContentHeaders,
+            ContentType,
+            Error as HttpError,
+            HttpConnection,
+            HttpHandler,
+            HttpMethod,
+            HttpResult,
+            ResponseHeaders,
+            TextEncoding,
+        },
+    },
+    domain::{
+        dto::SystemInformation,
+        ports::{
+            FirmwareError,
+            LightIdentifier,
+            LightQueueReader,
+            LightStateChanger,
+        },
     },
-    domain::{dto::SystemInformation, ports::LightStateChanger},
 };
 
+/// Request header with the expected MD5 digest of an OTA image, in hex
+const OTA_MD5_HEADER: &str = "x-image-md5";
+
 // ============================================================================
 // HTTP API DTOs
 // These expose color_order as a separate field while the internal config
@@ -144,8 +158,17 @@ impl HttpHandler for FactoryHttpController {
             (HttpMethod::Post, "/api/light/test") => {
                 handle_light_test(&mut conn).await
             }
+            (HttpMethod::Post, "/api/light/identify") => {
+                handle_light_identify(&mut conn).await
+            }
             (HttpMethod::Post, "/api/boot") => handle_boot(&mut conn).await,
+            (HttpMethod::Post, "/api/reset") => {
+                handle_factory_reset(&mut conn).await
+            }
             (HttpMethod::Post, "/api/ota") => handle_ota_update(&mut conn).await,
+            (HttpMethod::Post, "/api/ota/validate") => {
+                handle_ota_validate(&mut conn).await
+            }
             _ => serve_404(&mut conn).await,
         }
     }
@@ -167,9 +190,27 @@ async fn handle_get_system_information(conn: &mut HttpConnection<'_>) -> HttpRes
     let mut build_version = String::<32>::new();
     build_version.push_str(config::BUILD_VERSION).unwrap();
 
+    let running_partition = {
+        let guard = super::FIRMWARE_USECASES.lock().await;
+        let usecases_ref = guard.borrow();
+        let usecases = usecases_ref.as_ref().unwrap();
+        usecases.running_partition()
+    };
+
+    let (pending_light_intents, dropped_light_intents) = {
+        let guard = LIGHT_STATE_SERVICE.lock().await;
+        let light_ref = guard.borrow();
+        light_ref.as_ref().map_or((0, 0), |light| {
+            (light.pending_intents(), light.dropped_intents())
+        })
+    };
+
     let system_information = SystemInformation {
         build_version,
         mac_address: config::mac_address(),
+        running_partition,
+        pending_light_intents,
+        dropped_light_intents,
     };
     conn.write_json(&system_information).await
 }
@@ -246,6 +287,19 @@ async fn handle_light_test(conn: &mut HttpConnection<'_>) -> HttpResult {
     Ok(())
 }
 
+async fn handle_light_identify(conn: &mut HttpConnection<'_>) -> HttpResult {
+    let guard = LIGHT_STATE_SERVICE.lock().await;
+    let light_ref = guard.borrow();
+    let light = light_ref.as_ref().ok_or(HttpError::NoData)?;
+    light
+        .identify(config::IDENTIFY_BLINKS)
+        .map_err(|_| HttpError::NoData)?;
+
+    conn.write_headers(&ResponseHeaders::success_no_content())
+        .await?;
+    Ok(())
+}
+
 async fn handle_boot(conn: &mut HttpConnection<'_>) -> HttpResult {
     let guard = super::FIRMWARE_USECASES.lock().await;
     let mut usecases_ref = guard.borrow_mut();
@@ -256,6 +310,18 @@ async fn handle_boot(conn: &mut HttpConnection<'_>) -> HttpResult {
     Ok(())
 }
 
+async fn handle_factory_reset(conn: &mut HttpConnection<'_>) -> HttpResult {
+    // Respond first: the reset reboots the device as soon as it is erased
+    conn.write_headers(&ResponseHeaders::success_no_content())
+        .await?;
+
+    let guard = CONFIGURATION_USECASES.lock().await;
+    let mut usecases_ref = guard.borrow_mut();
+    let usecases = usecases_ref.as_mut().unwrap();
+    usecases.factory_reset().map_err(|_| HttpError::NoData)?;
+    Ok(())
+}
+
 async fn handle_ota_update(conn: &mut HttpConnection<'_>) -> HttpResult {
     let guard = super::FIRMWARE_USECASES.lock().await;
     let mut usecases_ref = guard.borrow_mut();
@@ -270,6 +336,38 @@ async fn handle_ota_update(conn: &mut HttpConnection<'_>) -> HttpResult {
     Ok(())
 }
 
+async fn handle_ota_validate(conn: &mut HttpConnection<'_>) -> HttpResult {
+    let expected_md5 = match conn.header(OTA_MD5_HEADER) {
+        Some(value) => match md5::from_hex(value) {
+            Some(digest) => Some(digest),
+            None => {
+                return conn.write_headers(&ResponseHeaders::bad_request()).await;
+            }
+        },
+        None => None,
+    };
+
+    let guard = super::FIRMWARE_USECASES.lock().await;
+    let usecases_ref = guard.borrow();
+    let usecases = usecases_ref.as_ref().unwrap();
+    let result = usecases
+        .validate_firmware_from_http(conn, expected_md5)
+        .await;
+    match result {
+        Ok(info) => conn.write_json(&info).await,
+        Err(err) => {
+            let code = match err {
+                FirmwareError::InvalidImage => 415,
+                FirmwareError::TooLarge => 413,
+                FirmwareError::DigestMismatch => 422,
+                FirmwareError::Read => 400,
+                _ => 500,
+            };
+            conn.write_headers(&ResponseHeaders::from_code(code)).await
+        }
+    }
+}
+
 async fn serve_404(conn: &mut HttpConnection<'_>) -> HttpResult {
     conn.write_headers(&ResponseHeaders::not_found()).await?;
     conn.write_body(b"Not Found").await
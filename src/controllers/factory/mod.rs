@@ -3,7 +3,7 @@ mod http;
 
 use core::cell::RefCell;
 
-pub use button::handle_boot_button_click;
+pub use button::{handle_boot_button_long_press, handle_boot_button_short_press};
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
 pub use http::FactoryHttpController;
 
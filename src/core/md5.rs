@@ -0,0 +1,247 @@
+//! MD5 Digest
+//!
+//! Minimal streaming MD5 (RFC 1321) for checking uploaded firmware images
+//! against the digest reported by the build tooling. Not for security use.
+
+use heapless::String;
+
+/// Length of an MD5 digest in bytes
+pub(crate) const MD5_LEN: usize = 16;
+
+/// Per-round left rotations
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+    4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// Per-round constants, `floor(abs(sin(i + 1)) * 2^32)`
+const CONSTANTS: [u32; 64] = [
+    0xD76A_A478,
+    0xE8C7_B756,
+    0x2420_70DB,
+    0xC1BD_CEEE,
+    0xF57C_0FAF,
+    0x4787_C62A,
+    0xA830_4613,
+    0xFD46_9501,
+    0x6980_98D8,
+    0x8B44_F7AF,
+    0xFFFF_5BB1,
+    0x895C_D7BE,
+    0x6B90_1122,
+    0xFD98_7193,
+    0xA679_438E,
+    0x49B4_0821,
+    0xF61E_2562,
+    0xC040_B340,
+    0x265E_5A51,
+    0xE9B6_C7AA,
+    0xD62F_105D,
+    0x0244_1453,
+    0xD8A1_E681,
+    0xE7D3_FBC8,
+    0x21E1_CDE6,
+    0xC337_07D6,
+    0xF4D5_0D87,
+    0x455A_14ED,
+    0xA9E3_E905,
+    0xFCEF_A3F8,
+    0x676F_02D9,
+    0x8D2A_4C8A,
+    0xFFFA_3942,
+    0x8771_F681,
+    0x6D9D_6122,
+    0xFDE5_380C,
+    0xA4BE_EA44,
+    0x4BDE_CFA9,
+    0xF6BB_4B60,
+    0xBEBF_BC70,
+    0x289B_7EC6,
+    0xEAA1_27FA,
+    0xD4EF_3085,
+    0x0488_1D05,
+    0xD9D4_D039,
+    0xE6DB_99E5,
+    0x1FA2_7CF8,
+    0xC4AC_5665,
+    0xF429_2244,
+    0x432A_FF97,
+    0xAB94_23A7,
+    0xFC93_A039,
+    0x655B_59C3,
+    0x8F0C_CC92,
+    0xFFEF_F47D,
+    0x8584_5DD1,
+    0x6FA8_7E4F,
+    0xFE2C_E6E0,
+    0xA301_4314,
+    0x4E08_11A1,
+    0xF753_7E82,
+    0xBD3A_F235,
+    0x2AD7_D2BB,
+    0xEB86_D391,
+];
+
+/// Streaming MD5 hasher
+#[derive(Debug, Clone)]
+pub(crate) struct Md5 {
+    state: [u32; 4],
+    block: [u8; 64],
+    block_len: usize,
+    length: u64,
+}
+
+impl Md5 {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476],
+            block: [0; 64],
+            block_len: 0,
+            length: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        for &byte in data {
+            self.push_byte(byte);
+        }
+    }
+
+    pub(crate) fn finalize(mut self) -> [u8; MD5_LEN] {
+        let bit_len = self.length.wrapping_mul(8);
+        self.push_byte(0x80);
+        while self.block_len != 56 {
+            self.push_byte(0);
+        }
+        for byte in bit_len.to_le_bytes() {
+            self.push_byte(byte);
+        }
+
+        let mut digest = [0u8; MD5_LEN];
+        for (i, word) in self.state.iter().enumerate() {
+            digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.block[self.block_len] = byte;
+        self.block_len += 1;
+        if self.block_len == self.block.len() {
+            self.process_block();
+            self.block_len = 0;
+        }
+    }
+
+    #[allow(clippy::many_single_char_names)]
+    fn process_block(&mut self) {
+        let mut m = [0u32; 16];
+        for (i, word) in self.block.chunks_exact(4).enumerate() {
+            m[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i {
+                0..=15 => ((b & c) | (!b & d), i),
+                16..=31 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                32..=47 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(CONSTANTS[i])
+                .wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i]));
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+    }
+}
+
+/// Format a digest as lowercase hex
+pub(crate) fn to_hex(digest: &[u8; MD5_LEN]) -> String<{ MD5_LEN * 2 }> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::new();
+    for byte in digest {
+        let _ = hex.push(char::from(HEX[usize::from(byte >> 4)]));
+        let _ = hex.push(char::from(HEX[usize::from(byte & 0x0F)]));
+    }
+    hex
+}
+
+/// Parse a digest from hex, in either case
+pub(crate) fn from_hex(hex: &str) -> Option<[u8; MD5_LEN]> {
+    let hex = hex.as_bytes();
+    if hex.len() != MD5_LEN * 2 {
+        return None;
+    }
+    let mut digest = [0u8; MD5_LEN];
+    for (byte, pair) in digest.iter_mut().zip(hex.chunks_exact(2)) {
+        let high = char::from(pair[0]).to_digit(16)?;
+        let low = char::from(pair[1]).to_digit(16)?;
+        *byte = u8::try_from(high << 4 | low).ok()?;
+    }
+    Some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn md5(data: &[u8]) -> [u8; MD5_LEN] {
+        let mut md5 = Md5::new();
+        md5.update(data);
+        md5.finalize()
+    }
+
+    #[test]
+    fn rfc_1321_test_suite() {
+        assert_eq!(
+            md5(b""),
+            *b"\xd4\x1d\x8c\xd9\x8f\x00\xb2\x04\xe9\x80\x09\x98\xec\xf8\x42\x7e"
+        );
+        assert_eq!(
+            md5(b"abc"),
+            *b"\x90\x01\x50\x98\x3c\xd2\x4f\xb0\xd6\x96\x3f\x7d\x28\xe1\x7f\x72"
+        );
+        assert_eq!(
+            md5(b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"),
+            *b"\x57\xed\xf4\xa2\x2b\xe3\xc9\x55\xac\x49\xda\x2e\x21\x07\xb6\x7a"
+        );
+    }
+
+    #[test]
+    fn chunked_updates_match_single_update() {
+        let data = [0xA5u8; 1000];
+        let mut md5_chunked = Md5::new();
+        for chunk in data.chunks(7) {
+            md5_chunked.update(chunk);
+        }
+        assert_eq!(md5_chunked.finalize(), md5(&data));
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let digest = md5(b"abc");
+        let hex = to_hex(&digest);
+        assert_eq!(hex.as_str(), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(from_hex(&hex), Some(digest));
+        assert_eq!(from_hex("900150983CD24FB0D6963F7D28E17F72"), Some(digest));
+    }
+
+    #[test]
+    fn invalid_hex_is_rejected() {
+        assert_eq!(from_hex(""), None);
+        assert_eq!(from_hex("900150983cd24fb0d6963f7d28e17f7"), None);
+        assert_eq!(from_hex("900150983cd24fb0d6963f7d28e17fzz"), None);
+    }
+}
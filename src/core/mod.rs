@@ -1,2 +1,3 @@
+pub(crate) mod md5;
 pub(crate) mod net;
-// pub(crate) mod sync;
\ No newline at end of file
+// pub(crate) mod sync;
@@ -15,10 +15,16 @@ use super::{
         ResponseHeaders,
         TargetWriter as _,
         find_content_length,
+        find_header,
         parse_request_line,
         read_heading,
     },
 };
+#[cfg(feature = "websocket")]
+use super::{
+    headers::find_websocket_key,
+    websocket::{WebSocket, WebSocketKey, compute_accept_key},
+};
 
 const HEADER_BUFFER_SIZE: usize = 512;
 const BODY_BUFFER_SIZE: usize = 1024;
@@ -47,6 +53,8 @@ pub struct HttpConnection<'a> {
     socket: TcpSocket<'a>,
     content_length: u32,
     received: u32,
+    #[cfg(feature = "websocket")]
+    websocket_key: Option<WebSocketKey>,
     header_end: usize,
     header_buf: Vec<u8, HEADER_BUFFER_SIZE>,
     body_buf: Vec<u8, BODY_BUFFER_SIZE>,
@@ -73,6 +81,9 @@ impl<'a> HttpConnection<'a> {
         let (method, raw_path, rest_headers) =
             parse_request_line(header_str).ok_or(Error::Parse)?;
         let content_length = find_content_length(rest_headers).unwrap_or(0);
+        #[cfg(feature = "websocket")]
+        let websocket_key = find_websocket_key(rest_headers)
+            .and_then(|key| WebSocketKey::try_from(key).ok());
 
         #[cfg(feature = "log")]
         println!(
@@ -90,10 +101,34 @@ impl<'a> HttpConnection<'a> {
             body_buf: Vec::new(),
             content_length,
             received: 0,
+            #[cfg(feature = "websocket")]
+            websocket_key,
             header_end,
         })
     }
 
+    /// Check if the request asks for a WebSocket upgrade
+    #[cfg(feature = "websocket")]
+    pub(crate) fn is_websocket_upgrade(&self) -> bool {
+        self.websocket_key.is_some()
+    }
+
+    /// Complete the WebSocket handshake and hand over the socket
+    ///
+    /// Fails with [`Error::Parse`] if the request is not a WebSocket upgrade.
+    #[cfg(feature = "websocket")]
+    pub(crate) async fn upgrade_websocket(mut self) -> Result<WebSocket<'a>, Error> {
+        let key = self.websocket_key.take().ok_or(Error::Parse)?;
+        let accept_key = compute_accept_key(key.as_str());
+        self.write_headers(&ResponseHeaders::switching_protocols(accept_key))
+            .await?;
+
+        #[cfg(feature = "log")]
+        println!("http: upgraded connection to websocket: path={}", self.path);
+
+        Ok(WebSocket::new(self.socket))
+    }
+
     /// Write the headers to the connection
     pub(crate) async fn write_headers(
         &mut self,
@@ -152,6 +187,15 @@ impl<'a> HttpConnection<'a> {
         Ok(data)
     }
 
+    /// Get a request header value by name (case-insensitive)
+    ///
+    /// Only available until the response headers are written.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        let headers = self.header_buf.get(..self.header_end)?;
+        let headers = core::str::from_utf8(headers).ok()?;
+        find_header(headers, name)
+    }
+
     /// Get request method and path
     pub(crate) fn route(&self) -> (HttpMethod, &'_ str) {
         (self.method, self.path.as_str())
@@ -2,10 +2,15 @@ use core::fmt::Write;
 
 use embassy_net::tcp::{Error as TcpError, TcpSocket};
 
+#[cfg(feature = "websocket")]
+use super::websocket::WebSocketAcceptKey;
+
 pub(crate) type StatusCode = u16;
 
 fn reason_phrase(code: StatusCode) -> &'static str {
     match code {
+        #[cfg(feature = "websocket")]
+        101 => "Switching Protocols",
         200 => "OK",
         201 => "Created",
         202 => "Accepted",
@@ -17,6 +22,8 @@ fn reason_phrase(code: StatusCode) -> &'static str {
         409 => "Conflict",
         410 => "Gone",
         413 => "Request Entity Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Entity",
         500 => "Internal Server Error",
         _ => "Unknown",
     }
@@ -73,6 +80,8 @@ impl ContentType {
 #[derive(Debug)]
 pub(super) enum ConnectionPolicy {
     Close,
+    #[cfg(feature = "websocket")]
+    Upgrade,
 }
 
 impl ConnectionPolicy {
@@ -80,6 +89,8 @@ impl ConnectionPolicy {
     pub(super) fn as_str(&self) -> &'static str {
         match self {
             ConnectionPolicy::Close => "close",
+            #[cfg(feature = "websocket")]
+            ConnectionPolicy::Upgrade => "Upgrade",
         }
     }
 }
@@ -158,6 +169,8 @@ pub(crate) struct ResponseHeaders {
     status: StatusCode,
     connection: ConnectionPolicy,
     content: Option<ContentHeaders>,
+    #[cfg(feature = "websocket")]
+    websocket_accept: Option<WebSocketAcceptKey>,
 }
 
 impl ResponseHeaders {
@@ -167,6 +180,8 @@ impl ResponseHeaders {
             status: 0,
             content: None,
             connection: ConnectionPolicy::Close,
+            #[cfg(feature = "websocket")]
+            websocket_accept: None,
         }
     }
 
@@ -200,6 +215,15 @@ impl ResponseHeaders {
         Self::from_code(400)
     }
 
+    /// Create WebSocket upgrade response headers with an accept key.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn switching_protocols(accept_key: WebSocketAcceptKey) -> Self {
+        let mut headers = Self::from_code(101);
+        headers.connection = ConnectionPolicy::Upgrade;
+        headers.websocket_accept = Some(accept_key);
+        headers
+    }
+
     /// Set the content headers.
     #[must_use]
     pub(crate) const fn with_content(mut self, content: ContentHeaders) -> Self {
@@ -225,6 +249,11 @@ impl TargetWriter for ResponseHeaders {
         }
 
         write!(writer, "Connection: {}\r\n", self.connection.as_str())?;
+        #[cfg(feature = "websocket")]
+        if let Some(accept_key) = &self.websocket_accept {
+            write!(writer, "Upgrade: websocket\r\n")?;
+            write!(writer, "Sec-WebSocket-Accept: {}\r\n", accept_key)?;
+        }
         write!(writer, "\r\n")?;
         Ok(())
     }
@@ -331,3 +360,31 @@ pub(super) fn find_content_length(header: &str) -> Option<u32> {
     esp_println::println!("http: Content-Length header not found");
     None
 }
+
+/// Find a header value by name (case-insensitive).
+///
+/// Returns the trimmed value if found, otherwise None.
+pub(super) fn find_header<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Find the WebSocket key in the header string.
+///
+/// Returns the `Sec-WebSocket-Key` value only if the request asks for a
+/// WebSocket upgrade (`Upgrade: websocket` and `Connection: Upgrade`).
+#[cfg(feature = "websocket")]
+pub(super) fn find_websocket_key(header: &str) -> Option<&str> {
+    let upgrade = find_header(header, "upgrade")?;
+    let connection = find_header(header, "connection")?;
+    let is_upgrade = upgrade.eq_ignore_ascii_case("websocket")
+        && connection
+            .split(',')
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+    if !is_upgrade {
+        return None;
+    }
+    find_header(header, "sec-websocket-key")
+}
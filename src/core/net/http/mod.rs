@@ -1,6 +1,8 @@
 pub(crate) mod connection;
 pub(crate) mod headers;
 pub(crate) mod server;
+#[cfg(feature = "websocket")]
+pub(crate) mod websocket;
 
 pub(crate) use connection::{AsyncChunkedReader, HttpConnection};
 pub(crate) use headers::{
@@ -19,6 +21,8 @@ pub enum Error {
     Parse,
     NoData,
     FormatHeaders,
+    #[cfg(feature = "websocket")]
+    TooLarge,
 }
 
 impl From<core::fmt::Error> for Error {
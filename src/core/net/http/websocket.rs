@@ -0,0 +1,506 @@
+//! WebSocket Protocol Implementation
+//!
+//! Provides the server side of the RFC 6455 opening handshake and a bounded,
+//! allocation-free framing layer for text and binary messages.
+//!
+//! Fragmented messages are not supported: every data frame must have the
+//! `FIN` bit set and fit into [`MAX_FRAME_SIZE`] bytes.
+//!
+//! Built only with the `websocket` feature and not used by any endpoint
+//! yet: the HTTP server handles one connection at a time, so a long-lived
+//! socket would block every other request.
+
+use embassy_net::tcp::TcpSocket;
+use embedded_io_async::Write as _;
+use heapless::String;
+
+use super::{Error, HttpResult};
+
+/// Maximum payload size of a single frame
+pub(crate) const MAX_FRAME_SIZE: usize = 512;
+
+/// Maximum accepted length of the `Sec-WebSocket-Key` header value
+pub(crate) const WEBSOCKET_KEY_MAX_LEN: usize = 32;
+
+/// Length of the base64-encoded `Sec-WebSocket-Accept` value
+const ACCEPT_KEY_LEN: usize = 28;
+
+/// GUID appended to the client key before hashing (RFC 6455, section 1.3)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Maximum size of a frame header sent by the server
+const SERVER_HEADER_MAX_SIZE: usize = 4;
+
+const FIN_BIT: u8 = 0x80;
+const MASK_BIT: u8 = 0x80;
+const OPCODE_MASK: u8 = 0x0F;
+const LENGTH_MASK: u8 = 0x7F;
+const LENGTH_16BIT: u8 = 126;
+const LENGTH_64BIT: u8 = 127;
+
+/// Type alias for the `Sec-WebSocket-Key` header value
+pub(crate) type WebSocketKey = String<WEBSOCKET_KEY_MAX_LEN>;
+
+/// Type alias for the `Sec-WebSocket-Accept` header value
+pub(crate) type WebSocketAcceptKey = String<ACCEPT_KEY_LEN>;
+
+/// WebSocket frame opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    /// Convert the opcode to its wire value.
+    pub(crate) const fn as_u8(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+
+    /// Parse the opcode from its wire value.
+    pub(crate) const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded frame header.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FrameHeader {
+    /// Final fragment flag
+    pub(crate) fin: bool,
+    /// Frame opcode
+    pub(crate) opcode: Opcode,
+    /// Payload length in bytes
+    pub(crate) payload_len: usize,
+    /// Masking key, if the payload is masked
+    pub(crate) mask: Option<[u8; 4]>,
+}
+
+/// Message received from the client.
+#[derive(Debug)]
+pub(crate) enum Message<'a> {
+    Text(&'a str),
+    Binary(&'a [u8]),
+    Close,
+}
+
+/// Compute the `Sec-WebSocket-Accept` value for a client key.
+pub(crate) fn compute_accept_key(key: &str) -> WebSocketAcceptKey {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+    base64_encode(&sha1.finalize())
+}
+
+/// Encode a server frame header into `out`.
+///
+/// Server frames are never masked. Returns the header length.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn encode_frame_header(
+    opcode: Opcode,
+    payload_len: usize,
+    out: &mut [u8; SERVER_HEADER_MAX_SIZE],
+) -> Result<usize, Error> {
+    if payload_len > MAX_FRAME_SIZE {
+        return Err(Error::TooLarge);
+    }
+    out[0] = FIN_BIT | opcode.as_u8();
+    if payload_len < usize::from(LENGTH_16BIT) {
+        out[1] = payload_len as u8;
+        return Ok(2);
+    }
+    out[1] = LENGTH_16BIT;
+    out[2..4].copy_from_slice(&(payload_len as u16).to_be_bytes());
+    Ok(4)
+}
+
+/// Decode a frame header from the start of `buf`.
+///
+/// Returns `Ok(None)` if more bytes are needed, otherwise the header and its
+/// length in bytes.
+pub(crate) fn decode_frame_header(
+    buf: &[u8],
+) -> Result<Option<(FrameHeader, usize)>, Error> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buf[0] & FIN_BIT != 0;
+    let opcode = Opcode::from_u8(buf[0] & OPCODE_MASK).ok_or(Error::Parse)?;
+    let masked = buf[1] & MASK_BIT != 0;
+
+    let mut pos = 2;
+    let payload_len = match buf[1] & LENGTH_MASK {
+        LENGTH_16BIT => {
+            if buf.len() < pos + 2 {
+                return Ok(None);
+            }
+            let len = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
+            pos += 2;
+            usize::from(len)
+        }
+        LENGTH_64BIT => {
+            if buf.len() < pos + 8 {
+                return Ok(None);
+            }
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&buf[pos..pos + 8]);
+            pos += 8;
+            usize::try_from(u64::from_be_bytes(len)).map_err(|_| Error::TooLarge)?
+        }
+        len => usize::from(len),
+    };
+
+    let mask = if masked {
+        if buf.len() < pos + 4 {
+            return Ok(None);
+        }
+        let mut mask = [0u8; 4];
+        mask.copy_from_slice(&buf[pos..pos + 4]);
+        pos += 4;
+        Some(mask)
+    } else {
+        None
+    };
+
+    let header = FrameHeader {
+        fin,
+        opcode,
+        payload_len,
+        mask,
+    };
+    Ok(Some((header, pos)))
+}
+
+/// Apply (or remove) the masking key to the payload in place.
+pub(crate) fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// WebSocket connection established after a successful upgrade.
+pub(crate) struct WebSocket<'a> {
+    socket: TcpSocket<'a>,
+}
+
+impl<'a> WebSocket<'a> {
+    /// Wrap an already upgraded socket.
+    pub(crate) fn new(socket: TcpSocket<'a>) -> Self {
+        Self { socket }
+    }
+
+    /// Send a text message
+    pub(crate) async fn send_text(&mut self, text: &str) -> HttpResult {
+        self.send_frame(Opcode::Text, text.as_bytes()).await
+    }
+
+    /// Send a binary message
+    pub(crate) async fn send_binary(&mut self, data: &[u8]) -> HttpResult {
+        self.send_frame(Opcode::Binary, data).await
+    }
+
+    /// Send a single unfragmented frame
+    pub(crate) async fn send_frame(
+        &mut self,
+        opcode: Opcode,
+        payload: &[u8],
+    ) -> HttpResult {
+        let mut header = [0u8; SERVER_HEADER_MAX_SIZE];
+        let header_len = encode_frame_header(opcode, payload.len(), &mut header)?;
+        self.socket.write_all(&header[..header_len]).await?;
+        self.socket.write_all(payload).await?;
+        self.socket.flush().await?;
+        Ok(())
+    }
+
+    /// Receive the next data message into `buf`.
+    ///
+    /// Pings are answered transparently and pongs are ignored. A close frame
+    /// is acknowledged and reported as [`Message::Close`].
+    pub(crate) async fn receive<'b>(
+        &mut self,
+        buf: &'b mut [u8],
+    ) -> Result<Message<'b>, Error> {
+        loop {
+            let header = self.read_frame_header().await?;
+            if !header.fin || header.opcode == Opcode::Continuation {
+                return Err(Error::Parse);
+            }
+            if header.payload_len > buf.len().min(MAX_FRAME_SIZE) {
+                return Err(Error::TooLarge);
+            }
+
+            let payload = &mut buf[..header.payload_len];
+            self.read_exact(payload).await?;
+            if let Some(mask) = header.mask {
+                apply_mask(payload, mask);
+            }
+
+            match header.opcode {
+                Opcode::Ping => {
+                    self.send_frame(Opcode::Pong, payload).await?;
+                }
+                Opcode::Pong | Opcode::Continuation => {}
+                Opcode::Close => {
+                    self.send_frame(Opcode::Close, &[]).await?;
+                    return Ok(Message::Close);
+                }
+                Opcode::Text => {
+                    let len = header.payload_len;
+                    let text = core::str::from_utf8(&buf[..len])
+                        .map_err(|_| Error::Parse)?;
+                    return Ok(Message::Text(text));
+                }
+                Opcode::Binary => {
+                    return Ok(Message::Binary(&buf[..header.payload_len]));
+                }
+            }
+        }
+    }
+
+    /// Send a close frame and close the socket
+    pub(crate) async fn close(mut self) -> HttpResult {
+        self.send_frame(Opcode::Close, &[]).await?;
+        self.socket.close();
+        Ok(())
+    }
+
+    /// Read and decode the next frame header from the socket
+    async fn read_frame_header(&mut self) -> Result<FrameHeader, Error> {
+        // 2 bytes base + 8 bytes extended length + 4 bytes mask
+        let mut buf = [0u8; 14];
+        let mut len = 2;
+        self.read_exact(&mut buf[..len]).await?;
+        loop {
+            if let Some((header, _)) = decode_frame_header(&buf[..len])? {
+                return Ok(header);
+            }
+            let needed = expected_header_len(&buf[..2]);
+            self.read_exact(&mut buf[len..needed]).await?;
+            len = needed;
+        }
+    }
+
+    /// Fill `buf` completely from the socket
+    async fn read_exact(&mut self, buf: &mut [u8]) -> HttpResult {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let n = self.socket.read(&mut buf[filled..]).await?;
+            if n == 0 {
+                return Err(Error::Closed);
+            }
+            filled += n;
+        }
+        Ok(())
+    }
+}
+
+/// Full header length implied by the first two header bytes
+fn expected_header_len(base: &[u8]) -> usize {
+    let extended = match base[1] & LENGTH_MASK {
+        LENGTH_16BIT => 2,
+        LENGTH_64BIT => 8,
+        _ => 0,
+    };
+    let mask = if base[1] & MASK_BIT != 0 { 4 } else { 0 };
+    2 + extended + mask
+}
+
+/// Encode bytes as padded base64.
+fn base64_encode<const N: usize>(data: &[u8]) -> String<N> {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+        let triple = (u32::from(b0) << 16) | (u32::from(b1) << 8) | u32::from(b2);
+
+        for i in 0..4 {
+            let ch = if i <= chunk.len() {
+                ALPHABET[((triple >> (18 - 6 * i)) & 0x3F) as usize]
+            } else {
+                b'='
+            };
+            let _ = out.push(char::from(ch));
+        }
+    }
+    out
+}
+
+/// Minimal SHA-1 implementation used for the opening handshake only.
+struct Sha1 {
+    state: [u32; 5],
+    block: [u8; 64],
+    block_len: usize,
+    length: u64,
+}
+
+impl Sha1 {
+    const fn new() -> Self {
+        Self {
+            state: [
+                0x6745_2301,
+                0xEFCD_AB89,
+                0x98BA_DCFE,
+                0x1032_5476,
+                0xC3D2_E1F0,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            length: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        for &byte in data {
+            self.push_byte(byte);
+        }
+    }
+
+    fn finalize(mut self) -> [u8; 20] {
+        let bit_len = self.length.wrapping_mul(8);
+        self.push_byte(0x80);
+        while self.block_len != 56 {
+            self.push_byte(0);
+        }
+        for byte in bit_len.to_be_bytes() {
+            self.push_byte(byte);
+        }
+
+        let mut digest = [0u8; 20];
+        for (i, word) in self.state.iter().enumerate() {
+            digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.block[self.block_len] = byte;
+        self.block_len += 1;
+        if self.block_len == self.block.len() {
+            self.process_block();
+            self.block_len = 0;
+        }
+    }
+
+    #[allow(clippy::many_single_char_names)]
+    fn process_block(&mut self) {
+        let mut w = [0u32; 80];
+        for (i, word) in self.block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_matches_known_digest() {
+        let mut sha1 = Sha1::new();
+        sha1.update(b"abc");
+        assert_eq!(
+            sha1.finalize(),
+            [
+                0xA9, 0x99, 0x3E, 0x36, 0x47, 0x06, 0x81, 0x6A, 0xBA, 0x3E, 0x25,
+                0x71, 0x78, 0x50, 0xC2, 0x6C, 0x9C, 0xD0, 0xD8, 0x9D,
+            ]
+        );
+    }
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64_encode::<8>(b"f").as_str(), "Zg==");
+        assert_eq!(base64_encode::<8>(b"fo").as_str(), "Zm8=");
+        assert_eq!(base64_encode::<8>(b"foo").as_str(), "Zm9v");
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        // RFC 6455, section 1.3
+        let accept_key = compute_accept_key("dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(accept_key.as_str(), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn encodes_short_text_frame() {
+        let mut header = [0u8; SERVER_HEADER_MAX_SIZE];
+        let len = encode_frame_header(Opcode::Text, 5, &mut header).unwrap();
+        assert_eq!(&header[..len], &[0x81, 0x05]);
+    }
+
+    #[test]
+    fn decodes_masked_text_frame() {
+        // RFC 6455, section 5.7: a single-frame masked text message "Hello"
+        let mut frame = [
+            0x81, 0x85, 0x37, 0xFA, 0x21, 0x3D, 0x7F, 0x9F, 0x4D, 0x51, 0x58,
+        ];
+        let (header, header_len) = decode_frame_header(&frame).unwrap().unwrap();
+        assert!(header.fin);
+        assert_eq!(header.opcode, Opcode::Text);
+        assert_eq!(header.payload_len, 5);
+        assert_eq!(header_len, 6);
+
+        let payload = &mut frame[header_len..];
+        apply_mask(payload, header.mask.unwrap());
+        assert_eq!(payload, b"Hello");
+    }
+
+    #[test]
+    fn decode_waits_for_full_header() {
+        assert!(decode_frame_header(&[0x81]).unwrap().is_none());
+        assert!(decode_frame_header(&[0x81, 0x85, 0x37]).unwrap().is_none());
+    }
+}
@@ -2,13 +2,16 @@ use heapless::String;
 use myrtio_light_composer::{EffectId, LightStateIntent, Rgb};
 use serde::{Deserialize, Serialize};
 
-use crate::{config::DeviceConfig, domain::entity::LightState};
+use crate::{
+    config::DeviceConfig,
+    domain::entity::{AppPartition, LightState},
+};
 
 /// Represents a user intent to change the light state.
 ///
 /// This is a domain-neutral representation of what the user wants to do,
 /// independent of the source (MQTT, button, HTTP, etc.).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LightChangeIntent {
     /// Turn on (Some(true)), turn off (Some(false)), or no change (None)
     pub power: Option<bool>,
@@ -98,6 +101,20 @@ impl From<LightState> for LightChangeIntent {
 pub struct SystemInformation {
     pub build_version: String<32>,
     pub mac_address: [u8; 6],
+    /// Partition the firmware runs from, `None` if it could not be read
+    pub running_partition: Option<AppPartition>,
+    /// Light intents waiting for the engine
+    pub pending_light_intents: usize,
+    /// Light intents dropped because the engine queue was full
+    pub dropped_light_intents: u32,
+}
+
+/// OTA image checked by a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OtaImageInfo {
+    pub size: u32,
+    /// Lowercase hex MD5 digest of the image
+    pub md5: String<32>,
 }
 
 #[derive(Debug)]
@@ -1,5 +1,8 @@
+use myrtio_light_composer::EffectId;
+use serde::{Deserialize, Serialize};
+
 /// Color mode
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorMode {
     Rgb,
     Temperature,
@@ -23,7 +26,7 @@ impl ColorMode {
 }
 
 /// Represents the light state.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LightState {
     pub power: bool,
     pub brightness: u8,
@@ -41,7 +44,19 @@ impl LightState {
             brightness: 20,
             color: (255, 255, 255),
             color_temp: 4000,
-            mode_id: 0, // static
+            mode_id: EffectId::Static as u8,
+            color_mode: ColorMode::Rgb,
+        }
+    }
+
+    /// Dim static white, used when booting in safe mode
+    pub const fn safe_mode() -> Self {
+        Self {
+            power: true,
+            brightness: 32,
+            color: (255, 255, 255),
+            color_temp: 4000,
+            mode_id: EffectId::Static as u8,
             color_mode: ColorMode::Rgb,
         }
     }
@@ -60,3 +75,25 @@ pub enum BootSlot {
     System,
     Factory,
 }
+
+/// Application partition a firmware image runs from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AppPartition {
+    #[serde(rename = "factory")]
+    Factory,
+    #[serde(rename = "ota_0")]
+    Ota0,
+    #[serde(rename = "ota_1")]
+    Ota1,
+}
+
+impl AppPartition {
+    /// Partition name as in the partition table
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Factory => "factory",
+            Self::Ota0 => "ota_0",
+            Self::Ota1 => "ota_1",
+        }
+    }
+}
@@ -30,12 +30,23 @@ pub trait LightConfigurationSetter {
     ) -> Result<(), ConfigurationError>;
 }
 
+pub trait FactoryResetter {
+    /// Erase the stored configuration and light state, then reboot into the
+    /// factory firmware
+    fn factory_reset(&mut self) -> Result<(), ConfigurationError>;
+}
+
 pub trait ConfigurationServicePort:
     ConfigurationReader + ConfigurationWriter + Sync + Send
 {
 }
 
 pub trait ConfigurationUsecasesPort:
-    ConfigurationReader + LightConfigurationSetter + ConfigurationWriter + Sync + Send
+    ConfigurationReader
+    + LightConfigurationSetter
+    + ConfigurationWriter
+    + FactoryResetter
+    + Sync
+    + Send
 {
 }
@@ -3,7 +3,13 @@ extern crate alloc;
 use alloc::boxed::Box;
 use core::{future::Future, pin::Pin};
 
-use crate::{core::net::http::HttpConnection, domain::entity::BootSlot};
+use crate::{
+    core::{md5::MD5_LEN, net::http::HttpConnection},
+    domain::{
+        dto::OtaImageInfo,
+        entity::{AppPartition, BootSlot},
+    },
+};
 
 /// Error type for the firmware operations
 #[derive(Debug)]
@@ -17,6 +23,12 @@ pub enum FirmwareError {
     Read,
     Activate,
     Flash,
+    /// The image does not start with the ESP app image magic byte
+    InvalidImage,
+    /// The image does not fit into the target partition
+    TooLarge,
+    /// The image MD5 digest differs from the expected one
+    DigestMismatch,
 }
 
 /// Trait for the HTTP firmware updater (object-safe)
@@ -31,6 +43,19 @@ pub trait HttpFirmwareUpdater {
     ) -> Pin<Box<dyn Future<Output = Result<(), FirmwareError>> + 's>>
     where
         'c: 's;
+
+    /// Validate a firmware image from HTTP without writing it (dry run)
+    ///
+    /// Consumes the whole body and checks the image magic, the received size,
+    /// that it fits into the next OTA partition and, if given, its MD5 digest.
+    /// Flash is never written.
+    fn validate_firmware_from_http<'s, 'c>(
+        &'s self,
+        conn: &'c mut HttpConnection<'_>,
+        expected_md5: Option<[u8; MD5_LEN]>,
+    ) -> Pin<Box<dyn Future<Output = Result<OtaImageInfo, FirmwareError>> + 's>>
+    where
+        'c: 's;
 }
 
 pub trait BootSectorWriter {
@@ -39,8 +64,14 @@ pub trait BootSectorWriter {
 }
 
 pub trait BootSectorReader {
-    /// Read the boot sector
-    fn read_boot_sector(&mut self) -> Result<BootSlot, FirmwareError>;
+    /// Read the partition selected in the OTA data
+    fn read_boot_sector(&mut self) -> Result<AppPartition, FirmwareError>;
+}
+
+pub trait RunningPartitionReader {
+    /// Partition the running firmware was booted from, as selected in the
+    /// OTA data at startup, `None` if unknown
+    fn running_partition(&self) -> Option<AppPartition>;
 }
 
 pub trait BootSectorSelector {
@@ -52,7 +83,7 @@ pub trait BootSectorSelector {
 }
 
 pub trait FirmwareHandler:
-    BootSectorSelector + HttpFirmwareUpdater + Sync + Send
+    BootSectorSelector + RunningPartitionReader + HttpFirmwareUpdater + Sync + Send
 {
 }
 
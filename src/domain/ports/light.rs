@@ -28,6 +28,21 @@ pub trait LightStateChanger {
     ) -> Result<(), LightError>;
 }
 
+/// Identify interface for the light
+pub trait LightIdentifier {
+    /// Flash the strip `blinks` times, then restore the current state
+    fn identify(&self, blinks: u8) -> Result<(), LightError>;
+}
+
+/// Backpressure diagnostics of the light engine intent queue
+pub trait LightQueueReader {
+    /// Number of intents waiting for the engine
+    fn pending_intents(&self) -> usize;
+
+    /// Number of intents dropped because the queue was full
+    fn dropped_intents(&self) -> u32;
+}
+
 pub trait LightConfigChanger {
     /// Apply a light config change intent
     fn set_config(&mut self, config: LightConfig) -> Result<(), LightError>;
@@ -35,13 +50,23 @@ pub trait LightConfigChanger {
 
 /// Trait for the light usecases state handler
 pub trait LightStateHandler:
-    LightStateReader + LightConfigChanger + LightStateChanger + Sync + Send
+    LightStateReader
+    + LightConfigChanger
+    + LightStateChanger
+    + LightIdentifier
+    + Sync
+    + Send
 {
 }
 
 /// Port interface for the light usecases
 pub trait LightUsecasesPort:
-    LightStateReader + LightConfigChanger + LightStateChanger + Sync + Send
+    LightStateReader
+    + LightConfigChanger
+    + LightStateChanger
+    + LightIdentifier
+    + Sync
+    + Send
 {
     fn apply_intent_and_persist(
         &mut self,
@@ -27,8 +27,14 @@ pub trait PersistentDataReader {
     ) -> Result<(LightState, DeviceConfig), PersistenceError>;
 }
 
+/// Eraser interface for the persistent data
+pub trait PersistentDataEraser {
+    /// Erase all persistent data, so the next read returns defaults
+    fn erase_persistent_data(&self) -> Result<(), PersistenceError>;
+}
+
 /// Trait for the persistence handler
 pub trait PersistentDataHandler:
-    PersistentDataWriter + PersistentDataReader + Sync + Send
+    PersistentDataWriter + PersistentDataReader + PersistentDataEraser + Sync + Send
 {
 }
@@ -1,6 +1,7 @@
 use core::cell::RefCell;
 
 use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::Instant;
 use esp_hal::{
     gpio::{Event, Input, InputConfig, InputPin, Io, Pull},
     handler,
@@ -8,60 +9,202 @@ use esp_hal::{
     ram,
 };
 
-/// Callback type for button click handler
+use crate::config::{BUTTON_DEBOUNCE, BUTTON_LONG_PRESS};
+
+/// Callback type for button press handlers
 type BootButtonCallback = fn();
 
+/// Button press classified by hold duration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    /// Released before [`BUTTON_LONG_PRESS`]
+    Short,
+    /// Held for at least [`BUTTON_LONG_PRESS`]
+    Long,
+}
+
+impl ButtonEvent {
+    /// Classify a press by its hold duration, rejecting contact bounces
+    fn from_hold(held: embassy_time::Duration) -> Option<Self> {
+        if held < BUTTON_DEBOUNCE {
+            None
+        } else if held < BUTTON_LONG_PRESS {
+            Some(Self::Short)
+        } else {
+            Some(Self::Long)
+        }
+    }
+}
+
+/// Track a button edge, returning the event completed by a release
+fn track_edge(
+    pressed_at: &mut Option<Instant>,
+    pressed: bool,
+    now: Instant,
+) -> Option<ButtonEvent> {
+    if pressed {
+        // Bounces while held keep the original press time
+        pressed_at.get_or_insert(now);
+        return None;
+    }
+    let held = now.saturating_duration_since(pressed_at.take()?);
+    ButtonEvent::from_hold(held)
+}
+
+/// Callbacks for button press handlers
+#[derive(Clone, Copy)]
+struct BootButtonCallbacks {
+    on_short_press: BootButtonCallback,
+    on_long_press: BootButtonCallback,
+}
+
 /// Button input pin
 static BUTTON: Mutex<CriticalSectionRawMutex, RefCell<Option<Input>>> =
     Mutex::new(RefCell::new(None));
 
-/// Callback for button click handler
-static CALLBACK: Mutex<
+/// Time of the last accepted press, cleared on release
+static PRESSED_AT: Mutex<CriticalSectionRawMutex, RefCell<Option<Instant>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Callbacks for button press handlers
+static CALLBACKS: Mutex<
     CriticalSectionRawMutex,
-    RefCell<Option<BootButtonCallback>>,
+    RefCell<Option<BootButtonCallbacks>>,
 > = Mutex::new(RefCell::new(None));
 
 /// Bind boot button to the system
 pub fn bind_boot_button(
     mux: peripherals::IO_MUX<'static>,
     pin: impl InputPin + 'static,
-    on_click: BootButtonCallback,
+    on_short_press: BootButtonCallback,
+    on_long_press: BootButtonCallback,
 ) {
     let mut io = Io::new(mux);
-    io.set_interrupt_handler(handle_button_click);
+    io.set_interrupt_handler(handle_button_edge);
 
     let config = InputConfig::default().with_pull(Pull::Up);
     let mut button = Input::new(pin, config);
-    button.listen(Event::FallingEdge);
+    button.listen(Event::AnyEdge);
 
     BUTTON.lock(|cell| {
         cell.borrow_mut().replace(button);
     });
-    CALLBACK.lock(|cell| {
-        cell.borrow_mut().replace(on_click);
+    CALLBACKS.lock(|cell| {
+        cell.borrow_mut().replace(BootButtonCallbacks {
+            on_short_press,
+            on_long_press,
+        });
     });
 }
 
-/// Handler for boot button click event
+/// Handler for boot button edge event
+///
+/// The button is active low: a falling edge starts a press and a rising
+/// edge ends it. The event is dispatched on release.
 #[handler]
 #[ram]
-fn handle_button_click() {
-    let is_button_interrupt = BUTTON.lock(|cell| {
+fn handle_button_edge() {
+    let pressed = BUTTON.lock(|cell| {
         let mut cell = cell.borrow_mut();
-        if let Some(button) = cell.as_mut() {
-            let is_set = button.is_interrupt_set();
-            button.clear_interrupt();
-            is_set
-        } else {
-            false
+        let button = cell.as_mut()?;
+        if !button.is_interrupt_set() {
+            return None;
         }
+        button.clear_interrupt();
+        Some(button.is_low())
     });
+    let Some(pressed) = pressed else {
+        return;
+    };
 
-    if is_button_interrupt {
-        CALLBACK.lock(|cell| {
-            if let Some(callback) = cell.borrow().as_ref() {
-                callback();
-            }
-        });
+    let now = Instant::now();
+    let event =
+        PRESSED_AT.lock(|cell| track_edge(&mut cell.borrow_mut(), pressed, now));
+    let Some(event) = event else {
+        return;
+    };
+
+    let callbacks = CALLBACKS.lock(|cell| *cell.borrow());
+    if let Some(callbacks) = callbacks {
+        match event {
+            ButtonEvent::Short => (callbacks.on_short_press)(),
+            ButtonEvent::Long => (callbacks.on_long_press)(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::Duration;
+
+    use super::*;
+
+    fn at(ms: u64) -> Instant {
+        Instant::from_millis(ms)
+    }
+
+    #[test]
+    fn hold_duration_is_classified() {
+        assert_eq!(ButtonEvent::from_hold(Duration::from_millis(0)), None);
+        assert_eq!(
+            ButtonEvent::from_hold(BUTTON_DEBOUNCE - Duration::from_millis(1)),
+            None
+        );
+        assert_eq!(
+            ButtonEvent::from_hold(BUTTON_DEBOUNCE),
+            Some(ButtonEvent::Short)
+        );
+        assert_eq!(
+            ButtonEvent::from_hold(BUTTON_LONG_PRESS - Duration::from_millis(1)),
+            Some(ButtonEvent::Short)
+        );
+        assert_eq!(
+            ButtonEvent::from_hold(BUTTON_LONG_PRESS),
+            Some(ButtonEvent::Long)
+        );
+    }
+
+    #[test]
+    fn press_and_release_emit_event_on_release() {
+        let mut pressed_at = None;
+        let release = BUTTON_DEBOUNCE.as_millis() + 1;
+
+        assert_eq!(track_edge(&mut pressed_at, true, at(0)), None);
+        assert_eq!(
+            track_edge(&mut pressed_at, false, at(release)),
+            Some(ButtonEvent::Short)
+        );
+        assert_eq!(pressed_at, None);
+
+        let release = 1000 + BUTTON_LONG_PRESS.as_millis();
+        assert_eq!(track_edge(&mut pressed_at, true, at(1000)), None);
+        assert_eq!(
+            track_edge(&mut pressed_at, false, at(release)),
+            Some(ButtonEvent::Long)
+        );
+    }
+
+    #[test]
+    fn bounces_are_ignored() {
+        let mut pressed_at = None;
+
+        // A bounce shorter than the debounce window is not a press
+        assert_eq!(track_edge(&mut pressed_at, true, at(0)), None);
+        assert_eq!(track_edge(&mut pressed_at, false, at(1)), None);
+
+        // Repeated press edges keep the original press time
+        let long = BUTTON_LONG_PRESS.as_millis();
+        assert_eq!(track_edge(&mut pressed_at, true, at(100)), None);
+        assert_eq!(track_edge(&mut pressed_at, true, at(100 + long - 1)), None);
+        assert_eq!(
+            track_edge(&mut pressed_at, false, at(100 + long)),
+            Some(ButtonEvent::Long)
+        );
+    }
+
+    #[test]
+    fn release_without_press_is_ignored() {
+        let mut pressed_at = None;
+        assert_eq!(track_edge(&mut pressed_at, false, at(500)), None);
     }
 }
@@ -2,6 +2,6 @@ mod boot_button;
 mod http_server;
 mod mqtt_client;
 
-pub use boot_button::bind_boot_button;
+pub use boot_button::{ButtonEvent, bind_boot_button};
 pub use http_server::run_http_server;
-pub use mqtt_client::start_mqtt_client;
+pub use mqtt_client::{MqttEndpoints, mqtt_endpoints, start_mqtt_client};
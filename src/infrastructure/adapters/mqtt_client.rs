@@ -1,7 +1,21 @@
+use core::cell::Cell;
+
 use embassy_executor::Spawner;
-use embassy_net::{IpAddress, Stack, dns::DnsQueryType, tcp::TcpSocket};
-use embassy_sync::channel::Channel;
-use embassy_time::Duration;
+use embassy_net::{
+    IpAddress,
+    IpEndpoint,
+    Stack,
+    dns::{self, DnsQueryType},
+    tcp::TcpSocket,
+};
+use embassy_sync::{
+    blocking_mutex::{
+        Mutex,
+        raw::{CriticalSectionRawMutex, RawMutex},
+    },
+    channel::Channel,
+};
+use embassy_time::{Duration, Instant, Timer};
 #[cfg(feature = "log")]
 use esp_println::println;
 use heapless::String;
@@ -19,10 +33,88 @@ use crate::{
 const MQTT_OUTBOX_DEPTH: usize = 4;
 const MQTT_MAX_TOPICS: usize = 8;
 const MQTT_BUF_SIZE: usize = 2048;
+const MQTT_SOCKET_BUF_SIZE: usize = 1024;
+
+/// Delay before reconnecting after a failed session
+const MQTT_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// Consecutive failures after which the long cool-down is applied
+const MQTT_MAX_FAILED_ATTEMPTS: u32 = 10;
+/// Delay applied every `MQTT_MAX_FAILED_ATTEMPTS` consecutive failures
+const MQTT_COOLDOWN_DELAY: Duration = Duration::from_secs(300);
+/// Session duration after which the failure counter is reset
+const MQTT_STABLE_SESSION: Duration = Duration::from_secs(60);
 
 static PUBLISH_CHANNEL: PublishRequestChannel<'static, MQTT_OUTBOX_DEPTH> =
     Channel::new();
 
+static MQTT_ENDPOINTS: EndpointsRecord<CriticalSectionRawMutex> =
+    EndpointsRecord::new();
+
+/// Socket endpoints of a broker connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MqttEndpoints {
+    /// Local address and port of the socket
+    pub local: IpEndpoint,
+    /// Broker address the socket connected to, as picked from the DNS answer
+    pub remote: IpEndpoint,
+}
+
+/// Returns the endpoints of the current broker connection, if connected
+pub fn mqtt_endpoints() -> Option<MqttEndpoints> {
+    MQTT_ENDPOINTS.get()
+}
+
+/// Endpoints captured when the socket connects, cleared when it drops
+struct EndpointsRecord<M: RawMutex> {
+    endpoints: Mutex<M, Cell<Option<MqttEndpoints>>>,
+}
+
+impl<M: RawMutex> EndpointsRecord<M> {
+    const fn new() -> Self {
+        Self {
+            endpoints: Mutex::new(Cell::new(None)),
+        }
+    }
+
+    fn connected(&self, local: Option<IpEndpoint>, remote: Option<IpEndpoint>) {
+        let endpoints = local
+            .zip(remote)
+            .map(|(local, remote)| MqttEndpoints { local, remote });
+        self.endpoints.lock(|cell| cell.set(endpoints));
+    }
+
+    /// Clear the record, returning the endpoints of the dropped connection
+    fn disconnected(&self) -> Option<MqttEndpoints> {
+        self.endpoints.lock(Cell::take)
+    }
+
+    fn get(&self) -> Option<MqttEndpoints> {
+        self.endpoints.lock(Cell::get)
+    }
+}
+
+/// Socket buffers allocated once and lent to every connection attempt
+struct SocketBuffers {
+    rx: [u8; MQTT_SOCKET_BUF_SIZE],
+    tx: [u8; MQTT_SOCKET_BUF_SIZE],
+}
+
+impl SocketBuffers {
+    const fn new() -> Self {
+        Self {
+            rx: [0; MQTT_SOCKET_BUF_SIZE],
+            tx: [0; MQTT_SOCKET_BUF_SIZE],
+        }
+    }
+
+    /// Lend the buffers to a new connection, wiping the previous one's data
+    fn lend(&mut self) -> (&mut [u8], &mut [u8]) {
+        self.rx.fill(0);
+        self.tx.fill(0);
+        (&mut self.rx, &mut self.tx)
+    }
+}
+
 pub fn start_mqtt_client(
     spawner: Spawner,
     stack: Stack<'static>,
@@ -43,42 +135,90 @@ pub async fn mqtt_client_task(
 ) {
     #[cfg(feature = "log")]
     println!("mqtt: starting runtime task");
-    let mut rx_buffer = [0u8; 1024];
-    let mut tx_buffer = [0u8; 1024];
-    let device_id = mk_static!(String<32>, config::device_id());
+    // Socket buffers are allocated once and reused by every connection
+    let buffers = mk_static!(SocketBuffers, SocketBuffers::new());
+    let client_id = mk_static!(String<32>, config::mqtt_client_id());
     #[cfg(feature = "log")]
-    println!("mqtt: device id: {}", device_id);
+    println!("mqtt: client id: {}", client_id);
+    // Starting sessions at a per-device phase keeps devices booted together
+    // from publishing their periodic state at the same moment.
+    let phase = config::state_publish_phase();
+    Timer::after(phase).await;
+    let mut failures = 0;
     loop {
-        if let Err(_e) = run_mqtt_client(
+        let started_at = Instant::now();
+        let (rx_buffer, tx_buffer) = buffers.lend();
+        let _result = run_mqtt_client(
             stack,
             module,
             &mqtt_config,
-            device_id,
-            &mut rx_buffer,
-            &mut tx_buffer,
+            client_id,
+            rx_buffer,
+            tx_buffer,
         )
-        .await
-        {
-            #[cfg(feature = "log")]
-            println!("mqtt: connection lost, reconnecting in 2s...");
-            embassy_time::Timer::after(Duration::from_secs(2)).await;
-        }
+        .await;
+
+        let _endpoints = MQTT_ENDPOINTS.disconnected();
+
+        let reconnect;
+        (failures, reconnect) = reconnect_delay(failures, started_at.elapsed());
+        let delay = reconnect + phase;
+        #[cfg(feature = "log")]
+        println!(
+            "mqtt: connection {:?} lost ({:?}, {} consecutive failures), \
+             reconnecting in {}ms...",
+            _endpoints,
+            _result,
+            failures,
+            delay.as_millis()
+        );
+        Timer::after(delay).await;
     }
 }
 
+/// Count a finished session and pick the delay before reconnecting
+///
+/// Returns the new number of consecutive failures. A session that stayed up
+/// for `MQTT_STABLE_SESSION` proves the configuration works and starts the
+/// count over; every `MQTT_MAX_FAILED_ATTEMPTS` failures the long cool-down
+/// is applied instead of the normal delay.
+fn reconnect_delay(failures: u32, session: Duration) -> (u32, Duration) {
+    let failures = if session >= MQTT_STABLE_SESSION {
+        1
+    } else {
+        failures.saturating_add(1)
+    };
+    let delay = if failures.is_multiple_of(MQTT_MAX_FAILED_ATTEMPTS) {
+        MQTT_COOLDOWN_DELAY
+    } else {
+        MQTT_RECONNECT_DELAY
+    };
+    (failures, delay)
+}
+
 async fn run_mqtt_client(
     stack: Stack<'static>,
     module: &mut dyn MqttModule,
     mqtt_config: &MqttConfig,
-    device_id: &'static str,
+    client_id: &'static str,
     rx_buffer: &mut [u8],
     tx_buffer: &mut [u8],
 ) -> Result<(), ()> {
+    let broker_addr = resolve_host(stack, mqtt_config.host.as_str())
+        .await
+        .map_err(|_e| {
+            #[cfg(feature = "log")]
+            println!(
+                "mqtt: failed to resolve broker host {}: {:?}",
+                mqtt_config.host, _e
+            );
+        })?;
+
+    // A fresh socket starts with empty buffers, so nothing from the previous
+    // connection is carried over.
     let mut socket = TcpSocket::new(stack, rx_buffer, tx_buffer);
     socket.set_timeout(Some(Duration::from_secs(60)));
 
-    let broker_addr = resolve_host(stack, mqtt_config.host.as_str()).await?;
-
     #[cfg(feature = "log")]
     println!(
         "mqtt: connecting to broker {:?}:{}...",
@@ -92,11 +232,13 @@ async fn run_mqtt_client(
         println!("mqtt: TCP connect failed: {:?}", _e);
         return Err(());
     }
+    MQTT_ENDPOINTS.connected(socket.local_endpoint(), socket.remote_endpoint());
     #[cfg(feature = "log")]
-    println!("mqtt: TCP socket connected");
+    println!("mqtt: TCP socket connected: {:?}", MQTT_ENDPOINTS.get());
 
     let transport = TcpTransport::new(socket, Duration::from_secs(30));
-    let mut options = MqttOptions::new(device_id).with_keep_alive(Duration::from_secs(15));
+    let mut options =
+        MqttOptions::new(client_id).with_keep_alive(Duration::from_secs(15));
     if !mqtt_config.username.is_empty() {
         options = options.with_credentials(
             mqtt_config.username.as_str(),
@@ -122,15 +264,127 @@ async fn run_mqtt_client(
     })
 }
 
+/// Broker host resolution error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResolveError {
+    /// Host is neither an IP literal nor a valid DNS name
+    ParseAndDnsFailed,
+    /// DNS query succeeded but returned no A records
+    NoRecords,
+    /// DNS query failed (timeout, server error)
+    QueryFailed,
+}
+
+impl From<dns::Error> for ResolveError {
+    fn from(error: dns::Error) -> Self {
+        match error {
+            dns::Error::InvalidName | dns::Error::NameTooLong => {
+                Self::ParseAndDnsFailed
+            }
+            dns::Error::Failed => Self::QueryFailed,
+        }
+    }
+}
+
 /// Resolves a hostname to an IP address
-async fn resolve_host(stack: Stack<'static>, host: &str) -> Result<IpAddress, ()> {
+async fn resolve_host(
+    stack: Stack<'static>,
+    host: &str,
+) -> Result<IpAddress, ResolveError> {
     if let Ok(ip) = host.parse::<embassy_net::Ipv4Address>() {
         return Ok(IpAddress::Ipv4(ip));
     }
 
-    let Ok(addresses) = stack.dns_query(host, DnsQueryType::A).await else {
-        return Err(());
-    };
+    let addresses = stack.dns_query(host, DnsQueryType::A).await?;
+
+    addresses.first().copied().ok_or(ResolveError::NoRecords)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHORT_SESSION: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn cooldown_after_max_failed_attempts() {
+        let mut failures = 0;
+        for attempt in 1..MQTT_MAX_FAILED_ATTEMPTS {
+            let delay;
+            (failures, delay) = reconnect_delay(failures, SHORT_SESSION);
+            assert_eq!(failures, attempt);
+            assert_eq!(delay, MQTT_RECONNECT_DELAY);
+        }
 
-    addresses.first().copied().ok_or(())
+        let (failures, delay) = reconnect_delay(failures, SHORT_SESSION);
+        assert_eq!(failures, MQTT_MAX_FAILED_ATTEMPTS);
+        assert_eq!(delay, MQTT_COOLDOWN_DELAY);
+
+        // Retries continue at the normal pace after the cool-down
+        let (_, delay) = reconnect_delay(failures, SHORT_SESSION);
+        assert_eq!(delay, MQTT_RECONNECT_DELAY);
+    }
+
+    #[test]
+    fn stable_session_resets_failures() {
+        let (failures, delay) =
+            reconnect_delay(MQTT_MAX_FAILED_ATTEMPTS - 1, MQTT_STABLE_SESSION);
+        assert_eq!(failures, 1);
+        assert_eq!(delay, MQTT_RECONNECT_DELAY);
+    }
+
+    #[test]
+    fn reconnect_reuses_wiped_buffers() {
+        let mut buffers = SocketBuffers::new();
+
+        let (rx, tx) = buffers.lend();
+        let (rx_ptr, tx_ptr) = (rx.as_ptr(), tx.as_ptr());
+        // Data left behind by the previous connection
+        rx.fill(0xAA);
+        tx.fill(0x55);
+
+        let (rx, tx) = buffers.lend();
+        assert_eq!(rx.as_ptr(), rx_ptr);
+        assert_eq!(tx.as_ptr(), tx_ptr);
+        assert_eq!(rx.len(), MQTT_SOCKET_BUF_SIZE);
+        assert!(rx.iter().chain(tx.iter()).all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn endpoints_are_recorded_on_connect() {
+        use embassy_net::Ipv4Address;
+        use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+        let local = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 50).into(), 49152);
+        let remote = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 2).into(), 1883);
+        let record = EndpointsRecord::<NoopRawMutex>::new();
+        assert_eq!(record.get(), None);
+
+        record.connected(Some(local), Some(remote));
+        let expected = MqttEndpoints { local, remote };
+        assert_eq!(record.get(), Some(expected));
+
+        assert_eq!(record.disconnected(), Some(expected));
+        assert_eq!(record.get(), None);
+
+        // A socket that lost its peer before the query reports nothing
+        record.connected(Some(local), None);
+        assert_eq!(record.get(), None);
+    }
+
+    #[test]
+    fn dns_errors_map_to_resolve_errors() {
+        assert_eq!(
+            ResolveError::from(dns::Error::InvalidName),
+            ResolveError::ParseAndDnsFailed
+        );
+        assert_eq!(
+            ResolveError::from(dns::Error::NameTooLong),
+            ResolveError::ParseAndDnsFailed
+        );
+        assert_eq!(
+            ResolveError::from(dns::Error::Failed),
+            ResolveError::QueryFailed
+        );
+    }
 }
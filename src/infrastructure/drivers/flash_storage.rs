@@ -2,17 +2,40 @@
 //!
 //! Flash is owned by the flash actor task; this driver uses a raw pointer
 //! (single-owner assumption) to perform synchronous flash operations.
+//!
+//! Records are stored in two alternating slots (A/B). A save always goes to
+//! the slot that does not hold the latest record: the payload is written
+//! first and the header (magic, sequence number, checksum) last, so a power
+//! loss mid-write leaves the previous record intact. Load picks the valid
+//! slot with the highest sequence number.
+//!
+//! Older firmware (including factory images already in the field) keeps
+//! writing a single-block record to the legacy address. Such a record is
+//! always newer than the slots, because it is erased once it has been
+//! migrated into a slot.
 
 use core::{marker::PhantomData, mem};
 
 use bytemuck::Pod;
-use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use embedded_storage::nor_flash::NorFlash;
 use esp_println::println;
 use esp_storage::FlashStorage;
 
-const MAGIC_HEADER: u16 = 0xBEEF;
-const MAGIC_HEADER_SIZE: usize = MAGIC_HEADER.to_le_bytes().len();
+/// Magic header of the single-block format used before A/B slots
+const LEGACY_MAGIC_HEADER: u16 = 0xBEEF;
+const LEGACY_MAGIC_HEADER_SIZE: usize = LEGACY_MAGIC_HEADER.to_le_bytes().len();
+
+/// Magic header of an A/B slot record
+const MAGIC_HEADER: u32 = 0xBEEF_0AB1;
+/// Slot header: magic (u32), sequence number (u32), checksum (u32)
+const HEADER_SIZE: usize = 12;
 const BLOCK_SIZE: u32 = 4096;
+const SLOT_COUNT: u32 = 2;
+
+/// Flash reads and writes must be aligned to this many bytes
+const WORD_SIZE: usize = 4;
+/// Size of the stack buffer used to stream records to and from flash
+const CHUNK_SIZE: usize = 64;
 
 #[derive(Debug)]
 pub(crate) enum StorageError {
@@ -20,72 +43,481 @@ pub(crate) enum StorageError {
     InvalidMagicHeader,
 }
 
+/// Header of a stored slot record
+#[derive(Debug, Clone, Copy)]
+struct SlotHeader {
+    sequence: u32,
+    checksum: u32,
+}
+
+impl SlotHeader {
+    fn parse(buffer: &[u8; HEADER_SIZE]) -> Option<Self> {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                buffer[offset],
+                buffer[offset + 1],
+                buffer[offset + 2],
+                buffer[offset + 3],
+            ])
+        };
+        if word(0) != MAGIC_HEADER {
+            return None;
+        }
+
+        Some(Self {
+            sequence: word(4),
+            checksum: word(8),
+        })
+    }
+
+    fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC_HEADER.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+}
+
 /// Persistent storage implementation using a storage driver.
-pub struct EspPersistentStorage<T: Pod> {
-    flash: *mut FlashStorage<'static>,
+pub struct EspPersistentStorage<T: Pod, F: NorFlash = FlashStorage<'static>> {
+    flash: *mut F,
     addr: u32,
+    legacy_addr: Option<u32>,
     _phantom: PhantomData<T>,
 }
 
-impl<T: Pod> EspPersistentStorage<T> {
-    pub fn new(flash: *mut FlashStorage<'static>, addr: u32) -> Self {
+// Offsets within a record are bounded by BLOCK_SIZE, so they fit in u32
+#[allow(clippy::cast_possible_truncation)]
+impl<T: Pod, F: NorFlash> EspPersistentStorage<T, F> {
+    /// Creates storage using two consecutive blocks starting at `addr`.
+    pub fn new(flash: *mut F, addr: u32) -> Self {
+        const {
+            assert!(HEADER_SIZE + mem::size_of::<T>() <= BLOCK_SIZE as usize);
+        }
+
         Self {
             flash,
             addr,
+            legacy_addr: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Migrate single-block records found at `addr` into the slots.
+    #[must_use]
+    pub fn with_legacy_addr(mut self, addr: u32) -> Self {
+        self.legacy_addr = Some(addr);
+        self
+    }
+
     /// Load persistent data from flash
     pub(crate) fn load(&self) -> Result<T, StorageError> {
-        let mut buffer = [0u8; BLOCK_SIZE as usize];
-
-        match unsafe { &mut *self.flash }.read(self.addr, &mut buffer) {
-            Ok(()) => {
-                let magic = u16::from_le_bytes([buffer[0], buffer[1]]);
-                if magic == MAGIC_HEADER {
-                    let data_end = MAGIC_HEADER_SIZE + mem::size_of::<T>();
-                    // Use pod_read_unaligned because data starts after 2-byte magic
-                    // header which may not be aligned to T's
-                    // alignment requirements
-                    let data: T = bytemuck::pod_read_unaligned(
-                        &buffer[MAGIC_HEADER_SIZE..data_end],
-                    );
-
-                    return Ok(data);
-                }
-                #[cfg(feature = "log")]
-                println!("Invalid magic header: {:?}", magic);
-            }
-            Err(e) => {
-                println!("{:?}", e);
-                return Err(StorageError::DriverError);
-            }
+        if let Some(data) = self.migrate_legacy()? {
+            return Ok(data);
         }
-        Err(StorageError::InvalidMagicHeader)
+
+        let Some((slot, _)) = self.latest_slot()? else {
+            return Err(StorageError::InvalidMagicHeader);
+        };
+        let mut data = T::zeroed();
+        self.read_bytes(
+            self.slot_addr(slot) + HEADER_SIZE as u32,
+            bytemuck::bytes_of_mut(&mut data),
+        )?;
+        Ok(data)
     }
 
     /// Save persistent data to flash
     ///
-    /// NOR flash requires erase before write. This erases the entire block
-    /// (4 KiB sector) before writing the data.
+    /// NOR flash requires erase before write. This erases the inactive slot
+    /// (4 KiB sector), writes the payload and then commits it by writing the
+    /// header with the next sequence number.
     pub(crate) fn save(&self, state: &T) -> Result<(), StorageError> {
+        let (slot, sequence) = match self.latest_slot()? {
+            Some((slot, sequence)) => {
+                ((slot + 1) % SLOT_COUNT, sequence.wrapping_add(1))
+            }
+            None => (0, 1),
+        };
+        let addr = self.slot_addr(slot);
         let flash = unsafe { &mut *self.flash };
 
         // Erase the block first (NOR flash can only flip 1→0, erase sets to 1)
         flash
-            .erase(self.addr, self.addr + BLOCK_SIZE)
+            .erase(addr, addr + BLOCK_SIZE)
             .map_err(|_| StorageError::DriverError)?;
 
-        let mut buffer: [u8; BLOCK_SIZE as usize] = [0xFFu8; BLOCK_SIZE as usize];
-
-        buffer[0..MAGIC_HEADER_SIZE].copy_from_slice(&MAGIC_HEADER.to_le_bytes());
-        let state_bytes = bytemuck::bytes_of(state);
-        let data_end = MAGIC_HEADER_SIZE + state_bytes.len();
-        buffer[MAGIC_HEADER_SIZE..data_end].copy_from_slice(state_bytes);
+        // Phase 1: payload. The header stays erased, so the slot is invalid
+        // until phase 2 completes.
+        let payload = bytemuck::bytes_of(state);
+        self.write_bytes(addr + HEADER_SIZE as u32, payload)?;
 
+        // Phase 2: header
+        let header = SlotHeader {
+            sequence,
+            checksum: checksum(sequence, payload),
+        };
         flash
-            .write(self.addr, &buffer)
+            .write(addr, &header.to_bytes())
             .map_err(|_| StorageError::DriverError)
     }
+
+    /// Erase both slots and the legacy record, if any
+    pub(crate) fn erase(&self) -> Result<(), StorageError> {
+        let flash = unsafe { &mut *self.flash };
+        flash
+            .erase(self.addr, self.slot_addr(SLOT_COUNT))
+            .map_err(|_| StorageError::DriverError)?;
+        if let Some(legacy_addr) = self.legacy_addr {
+            flash
+                .erase(legacy_addr, legacy_addr + BLOCK_SIZE)
+                .map_err(|_| StorageError::DriverError)?;
+        }
+        Ok(())
+    }
+
+    /// Move a legacy record into the next slot and erase it
+    ///
+    /// The slot is written before the legacy block is erased, so a power
+    /// loss in between only repeats the migration on the next load.
+    fn migrate_legacy(&self) -> Result<Option<T>, StorageError> {
+        let Some(legacy_addr) = self.legacy_addr else {
+            return Ok(None);
+        };
+        let mut magic = [0u8; LEGACY_MAGIC_HEADER_SIZE];
+        self.read_bytes(legacy_addr, &mut magic)?;
+        if u16::from_le_bytes(magic) != LEGACY_MAGIC_HEADER {
+            return Ok(None);
+        }
+
+        let mut data = T::zeroed();
+        self.read_bytes(
+            legacy_addr + LEGACY_MAGIC_HEADER_SIZE as u32,
+            bytemuck::bytes_of_mut(&mut data),
+        )?;
+        self.save(&data)?;
+        unsafe { &mut *self.flash }
+            .erase(legacy_addr, legacy_addr + BLOCK_SIZE)
+            .map_err(|_| StorageError::DriverError)?;
+
+        #[cfg(feature = "log")]
+        println!("storage: migrated legacy record");
+        Ok(Some(data))
+    }
+
+    /// Returns the slot index and sequence number of the latest valid record
+    fn latest_slot(&self) -> Result<Option<(u32, u32)>, StorageError> {
+        let mut latest: Option<(u32, u32)> = None;
+
+        for slot in 0..SLOT_COUNT {
+            let addr = self.slot_addr(slot);
+            let mut header = [0u8; HEADER_SIZE];
+            self.read_bytes(addr, &mut header)?;
+            let Some(header) = SlotHeader::parse(&header) else {
+                continue;
+            };
+            if header.checksum != self.payload_checksum(addr, header.sequence)? {
+                #[cfg(feature = "log")]
+                println!("storage: slot {} checksum mismatch", slot);
+                continue;
+            }
+            let is_newer = latest.is_none_or(|(_, sequence)| {
+                header.sequence.wrapping_sub(sequence).cast_signed() > 0
+            });
+            if is_newer {
+                latest = Some((slot, header.sequence));
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// Checksum of the payload stored in the slot at `addr`
+    fn payload_checksum(
+        &self,
+        addr: u32,
+        sequence: u32,
+    ) -> Result<u32, StorageError> {
+        let mut crc = Crc32::new();
+        crc.update(&sequence.to_le_bytes());
+
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < mem::size_of::<T>() {
+            let len = (mem::size_of::<T>() - offset).min(CHUNK_SIZE);
+            self.read_bytes(
+                addr + (HEADER_SIZE + offset) as u32,
+                &mut chunk[..len],
+            )?;
+            crc.update(&chunk[..len]);
+            offset += len;
+        }
+
+        Ok(crc.finish())
+    }
+
+    /// Read `out.len()` bytes starting at `addr` using word-aligned reads
+    fn read_bytes(&self, addr: u32, out: &mut [u8]) -> Result<(), StorageError> {
+        let flash = unsafe { &mut *self.flash };
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let lead = addr as usize % WORD_SIZE;
+        let mut chunk_addr = addr - lead as u32;
+        let mut filled = 0;
+
+        while filled < out.len() {
+            let skip = if filled == 0 { lead } else { 0 };
+            let take = (out.len() - filled).min(CHUNK_SIZE - skip);
+            let len = (skip + take).next_multiple_of(WORD_SIZE);
+            flash.read(chunk_addr, &mut chunk[..len]).map_err(|e| {
+                println!("{:?}", e);
+                StorageError::DriverError
+            })?;
+            out[filled..filled + take].copy_from_slice(&chunk[skip..skip + take]);
+            filled += take;
+            chunk_addr += len as u32;
+        }
+
+        Ok(())
+    }
+
+    /// Write `data` to erased flash at the word-aligned `addr`
+    ///
+    /// The tail is padded with 0xFF, which leaves the erased bytes as is.
+    fn write_bytes(&self, addr: u32, data: &[u8]) -> Result<(), StorageError> {
+        let flash = unsafe { &mut *self.flash };
+        let mut chunk = [0xFFu8; CHUNK_SIZE];
+        let mut chunk_addr = addr;
+
+        for part in data.chunks(CHUNK_SIZE) {
+            let len = part.len().next_multiple_of(WORD_SIZE);
+            chunk[..part.len()].copy_from_slice(part);
+            chunk[part.len()..len].fill(0xFF);
+            flash
+                .write(chunk_addr, &chunk[..len])
+                .map_err(|_| StorageError::DriverError)?;
+            chunk_addr += len as u32;
+        }
+
+        Ok(())
+    }
+
+    const fn slot_addr(&self, slot: u32) -> u32 {
+        self.addr + slot * BLOCK_SIZE
+    }
+}
+
+/// CRC-32 (IEEE) of the sequence number followed by the payload
+fn checksum(sequence: u32, payload: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(&sequence.to_le_bytes());
+    crc.update(payload);
+    crc.finish()
+}
+
+/// Incremental CRC-32 (IEEE)
+struct Crc32(u32);
+
+impl Crc32 {
+    const fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u32::from(byte);
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+    }
+
+    const fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    use super::*;
+
+    const LEGACY_ADDR: u32 = 0;
+    const SLOTS_ADDR: u32 = BLOCK_SIZE;
+    const FLASH_SIZE: usize = 3 * BLOCK_SIZE as usize;
+
+    /// Record large enough to span several chunks
+    type Record = [u8; 150];
+
+    /// RAM-backed NOR flash that can simulate a power loss
+    struct RamFlash {
+        data: [u8; FLASH_SIZE],
+        /// Number of writes that succeed before the power is cut
+        writes_left: Option<usize>,
+    }
+
+    impl RamFlash {
+        fn new() -> Self {
+            Self {
+                data: [0xFF; FLASH_SIZE],
+                writes_left: None,
+            }
+        }
+    }
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = WORD_SIZE;
+
+        fn read(
+            &mut self,
+            offset: u32,
+            bytes: &mut [u8],
+        ) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            if !offset.is_multiple_of(WORD_SIZE)
+                || !bytes.len().is_multiple_of(WORD_SIZE)
+            {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            FLASH_SIZE
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = WORD_SIZE;
+        const ERASE_SIZE: usize = BLOCK_SIZE as usize;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.data[from as usize..to as usize].fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            if !offset.is_multiple_of(WORD_SIZE)
+                || !bytes.len().is_multiple_of(WORD_SIZE)
+            {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            if let Some(writes_left) = &mut self.writes_left {
+                if *writes_left == 0 {
+                    return Err(NorFlashErrorKind::Other);
+                }
+                *writes_left -= 1;
+            }
+            for (cell, byte) in self.data[offset..].iter_mut().zip(bytes) {
+                *cell &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn storage(flash: &mut RamFlash) -> EspPersistentStorage<Record, RamFlash> {
+        EspPersistentStorage::new(core::ptr::from_mut(flash), SLOTS_ADDR)
+            .with_legacy_addr(LEGACY_ADDR)
+    }
+
+    fn record(value: u8) -> Record {
+        let mut record = [0u8; 150];
+        for (byte, i) in record.iter_mut().zip(0u8..) {
+            *byte = value.wrapping_add(i);
+        }
+        record
+    }
+
+    /// Write a record the way older factory firmware did
+    fn write_legacy(flash: &mut RamFlash, legacy: &Record) {
+        flash.data[..LEGACY_MAGIC_HEADER_SIZE]
+            .copy_from_slice(&LEGACY_MAGIC_HEADER.to_le_bytes());
+        flash.data[LEGACY_MAGIC_HEADER_SIZE..][..legacy.len()]
+            .copy_from_slice(legacy);
+    }
+
+    /// Number of writes needed for the payload of one record
+    fn payload_writes() -> usize {
+        mem::size_of::<Record>().div_ceil(CHUNK_SIZE)
+    }
+
+    #[test]
+    fn load_returns_latest_record() {
+        let mut flash = RamFlash::new();
+        let storage = storage(&mut flash);
+        assert!(storage.load().is_err());
+
+        storage.save(&record(1)).unwrap();
+        storage.save(&record(2)).unwrap();
+        storage.save(&record(3)).unwrap();
+
+        assert_eq!(storage.load().unwrap(), record(3));
+    }
+
+    #[test]
+    fn crash_between_phases_keeps_previous_record() {
+        let mut flash = RamFlash::new();
+        storage(&mut flash).save(&record(1)).unwrap();
+
+        // Power is lost after the payload, before the header is written
+        flash.writes_left = Some(payload_writes());
+        assert!(storage(&mut flash).save(&record(2)).is_err());
+
+        flash.writes_left = None;
+        assert_eq!(storage(&mut flash).load().unwrap(), record(1));
+    }
+
+    #[test]
+    fn crash_during_payload_keeps_previous_record() {
+        let mut flash = RamFlash::new();
+        storage(&mut flash).save(&record(1)).unwrap();
+        storage(&mut flash).save(&record(2)).unwrap();
+
+        flash.writes_left = Some(1);
+        assert!(storage(&mut flash).save(&record(3)).is_err());
+
+        flash.writes_left = None;
+        let storage = storage(&mut flash);
+        assert_eq!(storage.load().unwrap(), record(2));
+
+        // The interrupted slot is reused by the next save
+        storage.save(&record(4)).unwrap();
+        assert_eq!(storage.load().unwrap(), record(4));
+    }
+
+    #[test]
+    fn legacy_record_is_migrated_into_a_slot() {
+        let mut flash = RamFlash::new();
+        storage(&mut flash).save(&record(1)).unwrap();
+
+        // Older factory firmware writes its record after the slots
+        let legacy = record(9);
+        write_legacy(&mut flash, &legacy);
+
+        assert_eq!(storage(&mut flash).load().unwrap(), legacy);
+        assert!(flash.data[..BLOCK_SIZE as usize].iter().all(|&b| b == 0xFF));
+        assert_eq!(storage(&mut flash).load().unwrap(), legacy);
+    }
+
+    #[test]
+    fn erase_clears_slots_and_legacy_record() {
+        let mut flash = RamFlash::new();
+        storage(&mut flash).save(&record(1)).unwrap();
+        storage(&mut flash).save(&record(2)).unwrap();
+        write_legacy(&mut flash, &record(9));
+
+        storage(&mut flash).erase().unwrap();
+
+        // Nothing is left to load, so the caller falls back to defaults
+        assert!(flash.data.iter().all(|&b| b == 0xFF));
+        assert!(storage(&mut flash).load().is_err());
+    }
 }
@@ -12,7 +12,7 @@ use myrtio_light_composer::{OutputDriver, Rgb};
 use smart_leds::SmartLedsWrite;
 use static_cell::make_static;
 
-use crate::config::ColorOrder;
+use crate::config::{ColorOrder, LED_SKIP_UNCHANGED_FRAMES};
 
 pub(crate) const MAX_LED_COUNT: usize = 128;
 
@@ -36,6 +36,47 @@ fn get_color_order() -> ColorOrder {
 /// to generate the precise timing signals required by WS2812B LEDs.
 pub(crate) struct EspLedDriver<'a> {
     adapter: SmartLedsAdapter<'a, { buffer_size(MAX_LED_COUNT) }>,
+    last_frame: FrameCache,
+}
+
+/// Last frame sent to the strip, used to skip unchanged frames
+struct FrameCache {
+    colors: [Rgb; MAX_LED_COUNT],
+    /// Number of LEDs in `colors`, `None` until the first write
+    len: Option<usize>,
+    /// Color order the frame was sent with
+    order: ColorOrder,
+}
+
+impl FrameCache {
+    const fn new(order: ColorOrder) -> Self {
+        Self {
+            colors: [Rgb { r: 0, g: 0, b: 0 }; MAX_LED_COUNT],
+            len: None,
+            order,
+        }
+    }
+
+    /// Returns `true` if the frame differs from the last one sent to the
+    /// strip, including a different LED count or color order
+    fn changed(&self, colors: &[Rgb], order: ColorOrder) -> bool {
+        if self.len != Some(colors.len()) || self.order != order {
+            return true;
+        }
+
+        self.colors
+            .iter()
+            .zip(colors)
+            .any(|(a, b)| a.r != b.r || a.g != b.g || a.b != b.b)
+    }
+
+    /// Remember the frame sent to the strip
+    fn store(&mut self, colors: &[Rgb], order: ColorOrder) {
+        let len = colors.len().min(MAX_LED_COUNT);
+        self.colors[..len].copy_from_slice(&colors[..len]);
+        self.len = Some(colors.len());
+        self.order = order;
+    }
 }
 
 impl<'a> EspLedDriver<'a> {
@@ -55,13 +96,26 @@ impl<'a> EspLedDriver<'a> {
         let rmt_buffer = make_static!(smart_led_buffer!(MAX_LED_COUNT));
         let adapter = SmartLedsAdapter::new(rmt.channel0, pin, rmt_buffer);
 
-        Self { adapter }
+        Self {
+            adapter,
+            last_frame: FrameCache::new(get_color_order()),
+        }
     }
 }
 
 impl OutputDriver for EspLedDriver<'static> {
     fn write(&mut self, colors: &[Rgb]) {
+        // WS2812 latches the last frame it received, so an unchanged frame
+        // (including a dark strip) does not need to be sent again. The next
+        // command changes the frame and resumes transmission. The chain
+        // protocol has no partial updates, so any change resends the whole
+        // frame.
         let order = get_color_order();
+        if LED_SKIP_UNCHANGED_FRAMES && !self.last_frame.changed(colors, order) {
+            return;
+        }
+        self.last_frame.store(colors, order);
+
         interrupt::free(|| {
             let reordered_colors = colors.iter().map(|c| {
                 let (r, g, b) = order.reorder(c.r, c.g, c.b);
@@ -71,3 +125,57 @@ impl OutputDriver for EspLedDriver<'static> {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+
+    #[test]
+    fn off_strip_idles_until_next_command() {
+        let order = ColorOrder::Grb;
+        let mut cache = FrameCache::new(order);
+        let off = [BLACK; 16];
+
+        // The first black frame is still sent to turn the strip off
+        assert!(cache.changed(&off, order));
+        cache.store(&off, order);
+        assert!(!cache.changed(&off, order));
+
+        let mut on = off;
+        on[0] = Rgb { r: 255, g: 0, b: 0 };
+        assert!(cache.changed(&on, order));
+    }
+
+    #[test]
+    fn color_order_change_resends_frame() {
+        let mut cache = FrameCache::new(ColorOrder::Grb);
+        let frame = [Rgb { r: 1, g: 2, b: 3 }; 4];
+        cache.store(&frame, ColorOrder::Grb);
+
+        assert!(!cache.changed(&frame, ColorOrder::Grb));
+        assert!(cache.changed(&frame, ColorOrder::Rgb));
+    }
+
+    #[test]
+    fn single_led_change_resends_frame() {
+        let order = ColorOrder::Grb;
+        let mut cache = FrameCache::new(order);
+        let mut frame = [Rgb {
+            r: 10,
+            g: 20,
+            b: 30,
+        }; 32];
+        cache.store(&frame, order);
+        assert!(!cache.changed(&frame, order));
+
+        frame[31].g = 21;
+        assert!(cache.changed(&frame, order));
+        cache.store(&frame, order);
+        assert!(!cache.changed(&frame, order));
+
+        // A strip resized to fewer LEDs changes even with the same colors
+        assert!(cache.changed(&frame[..16], order));
+    }
+}
@@ -9,7 +9,10 @@ use esp_bootloader_esp_idf::{
 };
 use esp_storage::FlashStorage;
 
-use crate::domain::{entity::BootSlot, ports};
+use crate::domain::{
+    entity::{AppPartition, BootSlot},
+    ports,
+};
 
 impl From<BootSlot> for AppPartitionSubType {
     fn from(slot: BootSlot) -> Self {
@@ -20,12 +23,15 @@ impl From<BootSlot> for AppPartitionSubType {
     }
 }
 
-impl From<AppPartitionSubType> for BootSlot {
-    fn from(sub_type: AppPartitionSubType) -> Self {
+impl TryFrom<AppPartitionSubType> for AppPartition {
+    type Error = ports::FirmwareError;
+
+    fn try_from(sub_type: AppPartitionSubType) -> Result<Self, Self::Error> {
         match sub_type {
-            AppPartitionSubType::Factory => BootSlot::Factory,
-            AppPartitionSubType::Ota0 => BootSlot::System,
-            _ => BootSlot::Factory,
+            AppPartitionSubType::Factory => Ok(AppPartition::Factory),
+            AppPartitionSubType::Ota0 => Ok(AppPartition::Ota0),
+            AppPartitionSubType::Ota1 => Ok(AppPartition::Ota1),
+            _ => Err(ports::FirmwareError::InvalidPartitionTable),
         }
     }
 }
@@ -66,11 +72,38 @@ impl ports::BootSectorWriter for BootManager {
 }
 
 impl ports::BootSectorReader for BootManager {
-    fn read_boot_sector(&mut self) -> Result<BootSlot, ports::FirmwareError> {
+    fn read_boot_sector(&mut self) -> Result<AppPartition, ports::FirmwareError> {
         let sub_type = self.with_ota(|mut ota| {
             ota.current_app_partition()
                 .map_err(|_| ports::FirmwareError::InvalidPartitionTable)
-        });
-        sub_type.map(core::convert::Into::into)
+        })?;
+        sub_type.try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ota_slots_map_to_their_partitions() {
+        assert_eq!(
+            AppPartition::try_from(AppPartitionSubType::Factory).ok(),
+            Some(AppPartition::Factory)
+        );
+        assert_eq!(
+            AppPartition::try_from(AppPartitionSubType::Ota0).ok(),
+            Some(AppPartition::Ota0)
+        );
+        assert_eq!(
+            AppPartition::try_from(AppPartitionSubType::Ota1).ok(),
+            Some(AppPartition::Ota1)
+        );
+    }
+
+    #[test]
+    fn missing_slots_are_rejected() {
+        assert!(AppPartition::try_from(AppPartitionSubType::Ota2).is_err());
+        assert!(AppPartition::try_from(AppPartitionSubType::Test).is_err());
     }
 }
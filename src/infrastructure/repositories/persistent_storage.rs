@@ -9,7 +9,12 @@ use crate::{
     domain::{
         dto::PersistentData,
         entity::{ColorMode, LightState},
-        ports::{PersistenceError, PersistentDataReader, PersistentDataWriter},
+        ports::{
+            PersistenceError,
+            PersistentDataEraser,
+            PersistentDataReader,
+            PersistentDataWriter,
+        },
     },
     infrastructure::drivers::EspPersistentStorage,
 };
@@ -70,6 +75,12 @@ impl PersistentDataWriter for AppPersistentStorage {
     }
 }
 
+impl PersistentDataEraser for AppPersistentStorage {
+    fn erase_persistent_data(&self) -> Result<(), PersistenceError> {
+        self.erase().map_err(|_| PersistenceError::DriverError)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Zeroable, Pod, Default)]
 #[repr(C)]
 struct PersistentLightState {
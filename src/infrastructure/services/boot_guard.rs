@@ -0,0 +1,125 @@
+//! Boot crash guard.
+//!
+//! Counts boots that crashed before reaching a stable uptime. The counter
+//! lives in persistent RTC fast memory, which survives panics, watchdog and
+//! software resets. It is not initialized on power-on either, so a magic word
+//! tells a written record apart from leftover RAM contents. Intentional
+//! reboots clear the counter first, so only crashes are counted.
+
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use esp_hal::ram;
+
+use crate::config::{BOOT_SAFE_MODE_THRESHOLD, BOOT_STABLE_UPTIME};
+
+/// Marks the record as written by this firmware rather than random RAM
+const BOOT_CRASH_MAGIC: u32 = 0x4352_5348;
+
+/// Crash record: magic and the number of unstable boots
+#[ram(unstable(rtc_fast, persistent))]
+static mut BOOT_CRASH_RECORD: [u32; 2] = [0; 2];
+
+/// Register the current boot and start the stable uptime timer.
+///
+/// Returns `true` if the previous `BOOT_SAFE_MODE_THRESHOLD` boots all
+/// crashed before `BOOT_STABLE_UPTIME`, so the app should start in safe mode.
+pub fn init_boot_guard(spawner: Spawner) -> bool {
+    let mut record = read_crash_record();
+    let safe_mode = register_boot(&mut record);
+    write_crash_record(record);
+
+    spawner
+        .spawn(boot_guard_task())
+        .expect("Failed to spawn boot guard task");
+
+    #[cfg(feature = "log")]
+    esp_println::println!(
+        "boot: {} unstable boots before this one",
+        crash_count(record).saturating_sub(1)
+    );
+
+    safe_mode
+}
+
+/// Clear the crash counter before an intentional reboot
+pub(super) fn clear_boot_guard() {
+    write_crash_record(crash_record(0));
+}
+
+/// Task for clearing the crash counter once the boot is stable
+#[embassy_executor::task]
+async fn boot_guard_task() {
+    Timer::after(BOOT_STABLE_UPTIME).await;
+    clear_boot_guard();
+
+    #[cfg(feature = "log")]
+    esp_println::println!("boot: stable uptime reached, crash counter cleared");
+}
+
+/// Count the current boot as unstable until proven otherwise.
+///
+/// Returns `true` if enough previous boots crashed to start in safe mode.
+fn register_boot(record: &mut [u32; 2]) -> bool {
+    let crashes = crash_count(*record);
+    *record = crash_record(crashes.saturating_add(1));
+    crashes >= BOOT_SAFE_MODE_THRESHOLD
+}
+
+fn crash_count([magic, count]: [u32; 2]) -> u32 {
+    if magic == BOOT_CRASH_MAGIC { count } else { 0 }
+}
+
+const fn crash_record(count: u32) -> [u32; 2] {
+    [BOOT_CRASH_MAGIC, count]
+}
+
+fn read_crash_record() -> [u32; 2] {
+    unsafe { core::ptr::read_volatile(&raw const BOOT_CRASH_RECORD) }
+}
+
+fn write_crash_record(record: [u32; 2]) {
+    unsafe {
+        core::ptr::write_volatile(&raw mut BOOT_CRASH_RECORD, record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uninitialized_record_counts_as_no_crashes() {
+        assert_eq!(crash_count([0, 0]), 0);
+        assert_eq!(crash_count([0xDEAD_BEEF, 7]), 0);
+
+        let mut record = [0xDEAD_BEEF, 7];
+        assert!(!register_boot(&mut record));
+        assert_eq!(crash_count(record), 1);
+    }
+
+    #[test]
+    fn safe_mode_after_threshold_unstable_boots() {
+        let mut record = crash_record(0);
+        for _ in 0..BOOT_SAFE_MODE_THRESHOLD {
+            assert!(!register_boot(&mut record));
+        }
+        assert!(register_boot(&mut record));
+        assert!(register_boot(&mut record));
+    }
+
+    #[test]
+    fn cleared_record_starts_over() {
+        let mut record = crash_record(BOOT_SAFE_MODE_THRESHOLD);
+        assert!(register_boot(&mut record));
+
+        record = crash_record(0);
+        assert!(!register_boot(&mut record));
+    }
+
+    #[test]
+    fn counter_saturates() {
+        let mut record = crash_record(u32::MAX);
+        assert!(register_boot(&mut record));
+        assert_eq!(crash_count(record), u32::MAX);
+    }
+}
@@ -1,10 +1,13 @@
 extern crate alloc;
 
 use alloc::boxed::Box;
-use core::{future::Future, pin::Pin};
+use core::{cell::Cell, future::Future, pin::Pin};
 
 use embassy_executor::Spawner;
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    channel::Channel,
+};
 use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
 use esp_bootloader_esp_idf::{
     ota::OtaImageState,
@@ -14,25 +17,40 @@ use esp_bootloader_esp_idf::{
 #[cfg(feature = "log")]
 use esp_println::println;
 
-use super::flash::FLASH_STORAGE;
+use super::{boot_guard::clear_boot_guard, flash::FLASH_STORAGE};
 use crate::{
-    core::net::http::{AsyncChunkedReader, HttpConnection},
+    core::{
+        md5::{self, MD5_LEN, Md5},
+        net::http::{AsyncChunkedReader, HttpConnection},
+    },
     domain::{
-        entity::BootSlot,
+        dto::OtaImageInfo,
+        entity::{AppPartition, BootSlot},
         ports::{
+            BootSectorReader,
             BootSectorSelector,
             BootSectorWriter as _,
             FirmwareError,
             FirmwareHandler,
             HttpFirmwareUpdater,
+            RunningPartitionReader,
         },
     },
     infrastructure::repositories::BootManager,
 };
 
 const ALIGN: usize = 4;
+/// First byte of an ESP app image
+const ESP_IMAGE_MAGIC: u8 = 0xE9;
 const ERASE_SECTOR: u32 = 4096;
 
+/// Partition selected in the OTA data when this firmware started, see
+/// [`selected_partition`] for when it differs from the running image
+static RUNNING_PARTITION: Mutex<
+    CriticalSectionRawMutex,
+    Cell<Option<AppPartition>>,
+> = Mutex::new(Cell::new(None));
+
 #[derive(Default, Clone, Copy)]
 pub struct FirmwareService;
 
@@ -42,7 +60,26 @@ impl FirmwareService {
     }
 }
 
+/// Read the partition the OTA data selects at startup
+///
+/// Later OTA data writes only take effect on the next boot, so at startup
+/// the selection names the partition the bootloader was asked to start.
+/// The running image itself is not inspected: if the bootloader rejected
+/// the selected image and fell back to another slot, this reports the
+/// selected slot rather than the one actually running.
+fn selected_partition(reader: &mut impl BootSectorReader) -> Option<AppPartition> {
+    reader.read_boot_sector().ok()
+}
+
 pub fn init_firmware(spawner: Spawner) -> FirmwareService {
+    let running = FLASH_STORAGE.try_lock().ok().and_then(|mut flash| {
+        let flash_ptr = core::ptr::from_mut(&mut *flash);
+        selected_partition(&mut BootManager::new(flash_ptr))
+    });
+    RUNNING_PARTITION.lock(|cell| cell.set(running));
+    #[cfg(feature = "log")]
+    println!("firmware: running from {:?}", running);
+
     spawner.spawn(boot_to_sector_task()).unwrap();
 
     FirmwareService
@@ -149,6 +186,108 @@ impl HttpFirmwareUpdater for FirmwareService {
             Ok(())
         })
     }
+
+    fn validate_firmware_from_http<'s, 'c>(
+        &'s self,
+        conn: &'c mut HttpConnection<'_>,
+        expected_md5: Option<[u8; MD5_LEN]>,
+    ) -> Pin<Box<dyn Future<Output = Result<OtaImageInfo, FirmwareError>> + 's>>
+    where
+        'c: 's,
+    {
+        Box::pin(async move {
+            let part_capacity = {
+                let mut flash = FLASH_STORAGE.lock().await;
+                let mut part_buffer = [0u8; PARTITION_TABLE_MAX_LEN];
+                let mut updater = OtaUpdater::new(&mut *flash, &mut part_buffer)
+                    .map_err(|_| FirmwareError::InvalidPartitionTable)?;
+                let (partition, _part_type) = updater
+                    .next_partition()
+                    .map_err(|_| FirmwareError::InvalidPartitionTable)?;
+                u32::try_from(partition.capacity()).unwrap_or(u32::MAX)
+            };
+
+            let content_length = conn.content_length();
+            #[cfg(feature = "log")]
+            println!(
+                "ota: dry run, content_length={}, capacity={}",
+                content_length, part_capacity
+            );
+
+            let mut validator = ImageValidator::new();
+            let mut is_eof = false;
+            while !is_eof {
+                conn.read_and_then(|chunk| {
+                    if chunk.is_empty() {
+                        is_eof = true;
+                    } else {
+                        validator.update(chunk);
+                    }
+                })
+                .await
+                .map_err(|_| FirmwareError::Read)?;
+            }
+
+            let result =
+                validator.finish(content_length, part_capacity, expected_md5);
+            #[cfg(feature = "log")]
+            println!("ota: dry run result: {:?}", result);
+            result
+        })
+    }
+}
+
+/// Streaming checks of an OTA image that never touch flash
+struct ImageValidator {
+    received: u32,
+    magic: Option<u8>,
+    md5: Md5,
+}
+
+impl ImageValidator {
+    const fn new() -> Self {
+        Self {
+            received: 0,
+            magic: None,
+            md5: Md5::new(),
+        }
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        if self.magic.is_none() {
+            self.magic = chunk.first().copied();
+        }
+        let len = u32::try_from(chunk.len()).unwrap_or(u32::MAX);
+        self.received = self.received.saturating_add(len);
+        self.md5.update(chunk);
+    }
+
+    /// Check the received image against the request and the target partition
+    fn finish(
+        self,
+        content_length: u32,
+        capacity: u32,
+        expected_md5: Option<[u8; MD5_LEN]>,
+    ) -> Result<OtaImageInfo, FirmwareError> {
+        if self.magic != Some(ESP_IMAGE_MAGIC) {
+            return Err(FirmwareError::InvalidImage);
+        }
+        if self.received != content_length {
+            return Err(FirmwareError::Read);
+        }
+        if self.received > capacity {
+            return Err(FirmwareError::TooLarge);
+        }
+        let digest = self.md5.finalize();
+        if expected_md5.is_some_and(|expected| expected != digest) {
+            return Err(FirmwareError::DigestMismatch);
+        }
+
+        Ok(OtaImageInfo {
+            size: self.received,
+            md5: md5::to_hex(&digest),
+        })
+    }
 }
 
 impl FirmwareService {
@@ -183,6 +322,12 @@ impl BootSectorSelector for FirmwareService {
     }
 }
 
+impl RunningPartitionReader for FirmwareService {
+    fn running_partition(&self) -> Option<AppPartition> {
+        RUNNING_PARTITION.lock(Cell::get)
+    }
+}
+
 impl FirmwareHandler for FirmwareService {}
 
 #[embassy_executor::task]
@@ -190,6 +335,7 @@ async fn boot_to_sector_task() {
     let slot = BOOT_TO_SECTOR_CHANNEL.receive().await;
     let mut service = FirmwareService;
     service.set_boot_sector(slot).await.unwrap();
+    clear_boot_guard();
     esp_hal::system::software_reset();
 }
 
@@ -240,3 +386,94 @@ fn write_aligned_data<F: embedded_storage::nor_flash::NorFlash>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// OTA data reader returning a fixed selection, `None` if unreadable
+    struct MockBootSector(Option<AppPartition>);
+
+    impl BootSectorReader for MockBootSector {
+        fn read_boot_sector(&mut self) -> Result<AppPartition, FirmwareError> {
+            self.0.ok_or(FirmwareError::InvalidPartitionTable)
+        }
+    }
+
+    #[test]
+    fn selected_ota_slot_is_reported() {
+        let mut slot0 = MockBootSector(Some(AppPartition::Ota0));
+        assert_eq!(selected_partition(&mut slot0), Some(AppPartition::Ota0));
+
+        let mut slot1 = MockBootSector(Some(AppPartition::Ota1));
+        assert_eq!(selected_partition(&mut slot1), Some(AppPartition::Ota1));
+    }
+
+    #[test]
+    fn unreadable_ota_data_is_unknown() {
+        let mut broken = MockBootSector(None);
+        assert_eq!(selected_partition(&mut broken), None);
+    }
+
+    /// Image with the app magic, followed by a known payload
+    fn image() -> [u8; 8] {
+        [ESP_IMAGE_MAGIC, b'a', b'b', b'c', 1, 2, 3, 4]
+    }
+
+    fn validate(
+        chunks: &[&[u8]],
+        content_length: u32,
+        capacity: u32,
+        expected_md5: Option<[u8; MD5_LEN]>,
+    ) -> Result<OtaImageInfo, FirmwareError> {
+        let mut validator = ImageValidator::new();
+        for chunk in chunks {
+            validator.update(chunk);
+        }
+        validator.finish(content_length, capacity, expected_md5)
+    }
+
+    fn digest_of(data: &[u8]) -> [u8; MD5_LEN] {
+        let mut md5 = Md5::new();
+        md5.update(data);
+        md5.finalize()
+    }
+
+    #[test]
+    fn reports_size_and_digest_of_chunked_image() {
+        let image = image();
+        let digest = digest_of(&image);
+
+        let info =
+            validate(&[&image[..3], &image[3..]], 8, 64, Some(digest)).unwrap();
+        assert_eq!(info.size, 8);
+        assert_eq!(info.md5, md5::to_hex(&digest));
+
+        let info = validate(&[&image], 8, 64, None).unwrap();
+        assert_eq!(info.md5, md5::to_hex(&digest));
+    }
+
+    #[test]
+    fn distinct_errors_for_each_check() {
+        let image = image();
+        let mut bad_magic = image;
+        bad_magic[0] = 0;
+
+        assert!(matches!(
+            validate(&[&bad_magic], 8, 64, None),
+            Err(FirmwareError::InvalidImage)
+        ));
+        assert!(matches!(
+            validate(&[&image[..5]], 8, 64, None),
+            Err(FirmwareError::Read)
+        ));
+        assert!(matches!(
+            validate(&[&image], 8, 4, None),
+            Err(FirmwareError::TooLarge)
+        ));
+        assert!(matches!(
+            validate(&[&image], 8, 64, Some([0; MD5_LEN])),
+            Err(FirmwareError::DigestMismatch)
+        ));
+    }
+}
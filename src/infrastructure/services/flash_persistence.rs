@@ -1,24 +1,38 @@
 use embassy_executor::Spawner;
-use embassy_futures::select::{Either, select};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_futures::select::{Either3, select3};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    channel::Channel,
+    signal::Signal,
+};
 use embassy_time::{Duration, Timer};
+use esp_storage::FlashStorage;
 
-use super::flash::FLASH_STORAGE;
+use super::{boot_guard::clear_boot_guard, flash::FLASH_STORAGE};
 use crate::{
-    config::{DeviceConfig, LIGHT_STATE_WRITE_DEBOUNCE},
+    config::{
+        CONFIGURATION_PARTITION_OFFSET,
+        DeviceConfig,
+        LIGHT_STATE_WRITE_DEBOUNCE,
+    },
     domain::{
         dto::PersistentData,
-        entity::LightState,
+        entity::{BootSlot, LightState},
         ports::{
+            BootSectorWriter as _,
             PersistenceError,
+            PersistentDataEraser,
             PersistentDataHandler,
             PersistentDataReader,
             PersistentDataWriter,
         },
     },
-    infrastructure::repositories::AppPersistentStorage,
+    infrastructure::repositories::{AppPersistentStorage, BootManager},
 };
 
+/// Address of the single-block record written by older firmware
+const LEGACY_STORAGE_ADDR: u32 = 0;
+
 /// Size of the persistent data channel
 const PERSISTENT_DATA_CHANNEL_SIZE: usize = 4;
 
@@ -29,6 +43,9 @@ type PersistentDataChannel =
 /// Channel for persisting persistent data
 pub(crate) static PERSISTENT_DATA_CHANNEL: PersistentDataChannel = Channel::new();
 
+/// Pending factory reset request, handled by the persistence task
+static FACTORY_RESET_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
 /// Service for persisting persistent data
 #[derive(Debug, Default, Clone, Copy)]
 pub struct PersistenceService;
@@ -68,13 +85,24 @@ impl PersistentDataReader for PersistenceService {
             .map_err(|_| PersistenceError::Busy)?;
 
         let flash_ptr = core::ptr::from_mut(&mut *flash);
-        let storage = AppPersistentStorage::new(flash_ptr, 0);
+        let storage = open_storage(flash_ptr);
         storage
             .read_persistent_data()
             .map_err(|_| PersistenceError::DriverError)
     }
 }
 
+impl PersistentDataEraser for PersistenceService {
+    /// Request a factory reset from the persistence task
+    ///
+    /// The task erases the persistent data and reboots into the factory
+    /// firmware. Safe to call from interrupt handlers.
+    fn erase_persistent_data(&self) -> Result<(), PersistenceError> {
+        FACTORY_RESET_SIGNAL.signal(());
+        Ok(())
+    }
+}
+
 impl PersistentDataHandler for PersistenceService {}
 
 /// Task for persisting persistent data
@@ -90,9 +118,10 @@ async fn light_state_persistence_task(debounce: Duration) {
     loop {
         let receive_fut = receiver.receive();
         let timer_fut = Timer::after(debounce);
+        let reset_fut = FACTORY_RESET_SIGNAL.wait();
 
-        match select(receive_fut, timer_fut).await {
-            Either::First(data) => match data {
+        match select3(receive_fut, timer_fut, reset_fut).await {
+            Either3::First(data) => match data {
                 PersistentData::LightState(state) => {
                     pending_state = Some(state);
                 }
@@ -106,7 +135,7 @@ async fn light_state_persistence_task(debounce: Duration) {
                         .await;
                 }
             },
-            Either::Second(()) => {
+            Either3::Second(()) => {
                 let Some(state) = pending_state.take() else {
                     continue;
                 };
@@ -117,17 +146,49 @@ async fn light_state_persistence_task(debounce: Duration) {
                 );
                 write_persistent_data(PersistentData::LightState(state)).await;
             }
+            Either3::Third(()) => factory_reset().await,
         }
     }
 }
 
+/// Erase the persistent data and reboot into the factory firmware
+///
+/// The flash stays locked until the reset, so neither the pending state nor
+/// a write still queued in the channel can land after the erase.
+async fn factory_reset() {
+    let mut flash = FLASH_STORAGE.lock().await;
+    let flash_ptr = core::ptr::from_mut(&mut *flash);
+
+    open_storage(flash_ptr)
+        .erase_persistent_data()
+        .expect("error erasing persistent data");
+    BootManager::new(flash_ptr)
+        .write_boot_sector(BootSlot::Factory)
+        .expect("error selecting factory firmware");
+
+    #[cfg(feature = "log")]
+    esp_println::println!("persistence: persistent data erased, rebooting");
+    clear_boot_guard();
+    esp_hal::system::software_reset();
+}
+
 /// Write the persistent data to the storage
 async fn write_persistent_data(data: PersistentData) {
     let mut flash = FLASH_STORAGE.lock().await;
     let flash_ptr = core::ptr::from_mut(&mut *flash);
-    let storage = AppPersistentStorage::new(flash_ptr, 0);
+    let storage = open_storage(flash_ptr);
 
     storage
         .write_persistent_data(data)
         .expect("error persisting persistent data");
 }
+
+/// Open the persistent storage in the configuration partition.
+///
+/// Older firmware, including factory images that OTA does not replace,
+/// stores a single record at the start of flash. Such a record is moved
+/// into the configuration partition on the next load.
+fn open_storage(flash: *mut FlashStorage<'static>) -> AppPersistentStorage {
+    AppPersistentStorage::new(flash, CONFIGURATION_PARTITION_OFFSET)
+        .with_legacy_addr(LEGACY_STORAGE_ADDR)
+}
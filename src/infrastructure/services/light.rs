@@ -1,6 +1,11 @@
-use core::sync::atomic::{AtomicU8, AtomicU16, Ordering};
+use core::sync::atomic::{AtomicU8, AtomicU16, AtomicU32, Ordering};
 
 use embassy_executor::Spawner;
+use embassy_sync::{
+    blocking_mutex::raw::{CriticalSectionRawMutex, RawMutex},
+    channel::Channel,
+    signal::Signal,
+};
 use embassy_time::{Instant, Timer};
 use esp_hal::{gpio::interconnect::PeripheralOutput, peripherals::RMT};
 use myrtio_light_composer::{
@@ -24,6 +29,7 @@ use crate::{
     config::{
         self,
         DEFAULT_TRANSITION_TIMINGS,
+        IDENTIFY_BLINK_PHASE,
         LED_COUNT_MAX,
         LightConfig,
         unpack_color_correction_rgb24,
@@ -35,6 +41,8 @@ use crate::{
         ports::{
             LightConfigChanger,
             LightError,
+            LightIdentifier,
+            LightQueueReader,
             LightStateChanger,
             LightStateHandler,
             LightStateReader,
@@ -52,6 +60,12 @@ const LIGHT_INTENT_CHANNEL_SIZE: usize = 10;
 static LIGHT_INTENT_CHANNEL: IntentChannel<LIGHT_INTENT_CHANNEL_SIZE> =
     IntentChannel::new();
 
+/// Number of intents dropped because the intent channel was full
+static DROPPED_INTENTS: AtomicU32 = AtomicU32::new(0);
+
+/// Pending identify request, holding the number of blinks
+static IDENTIFY_SIGNAL: Signal<CriticalSectionRawMutex, u8> = Signal::new();
+
 /// Global thread-safe lock-free light state
 static LIGHT_STATE: AtomicLightState =
     AtomicLightState::from_state(&LightState::new());
@@ -68,9 +82,10 @@ impl LightStateReader for LightStateService {
 impl LightStateChanger for LightStateService {
     fn apply_light_intent(
         &self,
-        intent: DomainLightChangeIntent,
+        mut intent: DomainLightChangeIntent,
     ) -> Result<(), LightError> {
         let mut state = LIGHT_STATE.get();
+        let was_on = state.power;
         if let Some(mode_id_raw) = intent.effect_id {
             state.mode_id = mode_id_raw;
         }
@@ -89,6 +104,7 @@ impl LightStateChanger for LightStateService {
             state.power = power;
         }
 
+        normalize_brightness(&mut intent, &state, was_on);
         let composer_intent = LightChangeIntent::State(intent.into());
         send_intent_sync(composer_intent)?;
         LIGHT_STATE.set(&state);
@@ -122,6 +138,25 @@ impl LightConfigChanger for LightStateService {
     }
 }
 
+impl LightIdentifier for LightStateService {
+    fn identify(&self, blinks: u8) -> Result<(), LightError> {
+        if blinks > 0 {
+            IDENTIFY_SIGNAL.signal(blinks);
+        }
+        Ok(())
+    }
+}
+
+impl LightQueueReader for LightStateService {
+    fn pending_intents(&self) -> usize {
+        LIGHT_INTENT_CHANNEL.len()
+    }
+
+    fn dropped_intents(&self) -> u32 {
+        DROPPED_INTENTS.load(Ordering::Relaxed)
+    }
+}
+
 impl LightStateHandler for LightStateService {}
 
 /// Atomic light state
@@ -174,6 +209,9 @@ impl AtomicLightState {
         self.brightness.store(state.brightness, Ordering::Relaxed);
         self.power.store(u8::from(state.power), Ordering::Relaxed);
         self.effect_id.store(state.mode_id, Ordering::Relaxed);
+        self.color_temp.store(state.color_temp, Ordering::Relaxed);
+        self.color_mode
+            .store(state.color_mode.as_u8(), Ordering::Relaxed);
         self.r.store(state.color.0, Ordering::Relaxed);
         self.g.store(state.color.1, Ordering::Relaxed);
         self.b.store(state.color.2, Ordering::Relaxed);
@@ -212,6 +250,9 @@ where
     spawner
         .spawn(light_engine_task(driver, intents, config))
         .expect("Failed to spawn light service task");
+    spawner
+        .spawn(light_identify_task())
+        .expect("Failed to spawn light identify task");
 
     LightStateService
 }
@@ -240,8 +281,214 @@ async fn light_engine_task(
     }
 }
 
+/// Task for running identify sequences
+///
+/// Blink intents go straight to the engine without touching `LIGHT_STATE`,
+/// so once the sequence ends the engine is restored from the stored state.
+/// This also picks up any change applied while the sequence was running.
+#[embassy_executor::task]
+async fn light_identify_task() {
+    let blink_on = DomainLightChangeIntent::new()
+        .with_power(true)
+        .with_brightness(u8::MAX)
+        .with_color(255, 255, 255)
+        .with_effect_id(EffectId::Static as u8);
+    let blink_off = DomainLightChangeIntent::new().with_power(false);
+
+    loop {
+        let blinks = IDENTIFY_SIGNAL.wait().await;
+        #[cfg(feature = "log")]
+        esp_println::println!("light: identify, {} blinks", blinks);
+
+        for _ in 0..blinks {
+            send_intent(LightChangeIntent::State(blink_on.clone().into())).await;
+            Timer::after(IDENTIFY_BLINK_PHASE).await;
+            send_intent(LightChangeIntent::State(blink_off.clone().into())).await;
+            Timer::after(IDENTIFY_BLINK_PHASE).await;
+        }
+
+        let restore = identify_restore_intent(&LIGHT_STATE.get());
+        send_intent(LightChangeIntent::State(restore.into())).await;
+    }
+}
+
+/// Intent restoring `state` once an identify sequence ends
+///
+/// The sequence ends with the strip off, so it is restored as if turning on.
+fn identify_restore_intent(state: &LightState) -> DomainLightChangeIntent {
+    let mut restore = DomainLightChangeIntent::from(state.clone());
+    normalize_brightness(&mut restore, state, false);
+    restore
+}
+
+/// Adjust the intent brightness to the resulting `state`
+///
+/// Brightness set while off is only stored, so the strip stays black until
+/// it is explicitly turned on with the stored level.
+fn normalize_brightness(
+    intent: &mut DomainLightChangeIntent,
+    state: &LightState,
+    was_on: bool,
+) {
+    if !state.power {
+        intent.brightness = None;
+    } else if !was_on && intent.brightness.is_none() {
+        intent.brightness = Some(state.brightness);
+    }
+}
+
+async fn send_intent(intent: LightChangeIntent) {
+    LIGHT_INTENT_CHANNEL.send(intent).await;
+}
+
 fn send_intent_sync(intent: LightChangeIntent) -> Result<(), LightError> {
-    LIGHT_INTENT_CHANNEL
-        .try_send(intent)
-        .map_err(|_| LightError::Busy)
+    try_send_counted(&LIGHT_INTENT_CHANNEL, &DROPPED_INTENTS, intent)
+}
+
+/// Queue an item without waiting, counting it in `dropped` if the channel
+/// is full
+fn try_send_counted<M: RawMutex, T, const N: usize>(
+    channel: &Channel<M, T, N>,
+    dropped: &AtomicU32,
+    item: T,
+) -> Result<(), LightError> {
+    channel.try_send(item).map_err(|_| {
+        let _dropped = dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        #[cfg(feature = "log")]
+        esp_println::println!(
+            "light: intent channel full, {} intents dropped",
+            _dropped
+        );
+        LightError::Busy
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::*;
+
+    #[test]
+    fn depth_reflects_pending_items() {
+        let channel = Channel::<NoopRawMutex, u8, 3>::new();
+        let dropped = AtomicU32::new(0);
+
+        assert_eq!(channel.len(), 0);
+        try_send_counted(&channel, &dropped, 1).unwrap();
+        try_send_counted(&channel, &dropped, 2).unwrap();
+        assert_eq!(channel.len(), 2);
+
+        assert_eq!(channel.try_receive().ok(), Some(1));
+        assert_eq!(channel.len(), 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn overflow_counts_dropped_items() {
+        let channel = Channel::<NoopRawMutex, u8, 2>::new();
+        let dropped = AtomicU32::new(0);
+
+        try_send_counted(&channel, &dropped, 1).unwrap();
+        try_send_counted(&channel, &dropped, 2).unwrap();
+        assert!(matches!(
+            try_send_counted(&channel, &dropped, 3),
+            Err(LightError::Busy)
+        ));
+        assert!(try_send_counted(&channel, &dropped, 4).is_err());
+
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(channel.len(), 2);
+        assert_eq!(channel.try_receive().ok(), Some(1));
+        assert_eq!(channel.try_receive().ok(), Some(2));
+    }
+
+    #[test]
+    fn atomic_state_keeps_color_mode_and_temperature() {
+        let temperature = LightState {
+            power: true,
+            color_temp: 2700,
+            color_mode: ColorMode::Temperature,
+            ..LightState::new()
+        };
+        let atomic = AtomicLightState::from_state(&temperature);
+        assert_eq!(atomic.get(), temperature);
+
+        let rgb = LightState {
+            color: (10, 20, 30),
+            color_mode: ColorMode::Rgb,
+            ..temperature.clone()
+        };
+        atomic.set(&rgb);
+        assert_eq!(atomic.get(), rgb);
+
+        atomic.set(&temperature);
+        assert_eq!(atomic.get(), temperature);
+    }
+
+    fn state(power: bool, brightness: u8) -> LightState {
+        LightState {
+            power,
+            brightness,
+            ..LightState::new()
+        }
+    }
+
+    #[test]
+    fn brightness_while_off_is_stripped() {
+        let mut intent = DomainLightChangeIntent::new().with_brightness(80);
+        normalize_brightness(&mut intent, &state(false, 80), false);
+        assert_eq!(intent, DomainLightChangeIntent::new());
+
+        let mut intent = DomainLightChangeIntent::new()
+            .with_power(false)
+            .with_brightness(80);
+        normalize_brightness(&mut intent, &state(false, 80), true);
+        assert_eq!(intent, DomainLightChangeIntent::new().with_power(false));
+    }
+
+    #[test]
+    fn turning_on_uses_stored_brightness() {
+        let mut intent = DomainLightChangeIntent::new().with_power(true);
+        normalize_brightness(&mut intent, &state(true, 42), false);
+        assert_eq!(
+            intent,
+            DomainLightChangeIntent::new()
+                .with_power(true)
+                .with_brightness(42)
+        );
+    }
+
+    #[test]
+    fn on_to_on_leaves_intent_unchanged() {
+        let intent = DomainLightChangeIntent::new().with_color(1, 2, 3);
+        let mut normalized = intent.clone();
+        normalize_brightness(&mut normalized, &state(true, 42), true);
+        assert_eq!(normalized, intent);
+
+        let intent = DomainLightChangeIntent::new().with_brightness(7);
+        let mut normalized = intent.clone();
+        normalize_brightness(&mut normalized, &state(true, 7), true);
+        assert_eq!(normalized, intent);
+    }
+
+    #[test]
+    fn identify_restores_the_state_before_it() {
+        let state = LightState {
+            color: (10, 20, 30),
+            ..state(true, 42)
+        };
+        let restore = identify_restore_intent(&state);
+        assert_eq!(restore.power, Some(true));
+        assert_eq!(restore.brightness, Some(42));
+        assert_eq!(restore.color, Some((10, 20, 30)));
+        assert_eq!(restore.effect_id, Some(state.mode_id));
+    }
+
+    #[test]
+    fn identify_leaves_an_off_light_dark() {
+        let restore = identify_restore_intent(&state(false, 42));
+        assert_eq!(restore.power, Some(false));
+        assert_eq!(restore.brightness, None);
+    }
 }
@@ -1,8 +1,10 @@
+mod boot_guard;
 mod flash;
 mod flash_firmware;
 mod flash_persistence;
 mod light;
 
+pub use boot_guard::init_boot_guard;
 pub use flash::init_flash_storage;
 pub use flash_firmware::{FirmwareService, init_firmware};
 pub use flash_persistence::{PersistenceService, init_persistence};